@@ -1,3 +1,4 @@
+use crate::expressions::string_value;
 use oxrdf::vocab::xsd;
 use polars::datatypes::DataType;
 use polars::prelude::{col, str_concat, Expr, GetOutput, IntoSeries};
@@ -109,6 +110,7 @@ pub fn max(solution_mappings: &SolutionMappings, column_context: &Context) -> (E
 }
 
 pub fn group_concat(
+    solution_mappings: &SolutionMappings,
     column_context: &Context,
     separator: &Option<String>,
     distinct: bool,
@@ -120,37 +122,31 @@ pub fn group_concat(
     } else {
         "".to_string()
     };
+    let expr_rdf_node_type = rdf_node_type_from_context(column_context, solution_mappings);
+    let value_expr = string_value(col(column_context.as_str()), expr_rdf_node_type);
     let out_expr = if distinct {
-        col(column_context.as_str())
+        value_expr
             .cast(DataType::String)
             .list()
             .0
             .apply(
                 move |s| {
                     Ok(Some(
-                        str_concat(
-                            s.unique_stable()
-                                .expect("Unique stable error")
-                                .str()
-                                .unwrap(),
-                            use_sep.as_str(),
-                            true,
-                        )
-                        .into_series(),
+                        str_concat(s.unique_stable()?.str()?, use_sep.as_str(), true).into_series(),
                     ))
                 },
                 GetOutput::from_type(DataType::String),
             )
             .first()
     } else {
-        col(column_context.as_str())
+        value_expr
             .cast(DataType::String)
             .list()
             .0
             .apply(
                 move |s| {
                     Ok(Some(
-                        str_concat(s.str().unwrap(), use_sep.as_str(), true).into_series(),
+                        str_concat(s.str()?, use_sep.as_str(), true).into_series(),
                     ))
                 },
                 GetOutput::from_type(DataType::String),
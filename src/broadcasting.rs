@@ -0,0 +1,9 @@
+//Literal arguments of functions mapped over several columns arrive as length one series, and are
+//broadcast over the rows of the other arguments
+pub fn broadcast_index(len: usize, i: usize) -> usize {
+    if len == 1 {
+        0
+    } else {
+        i
+    }
+}
@@ -0,0 +1,483 @@
+use crate::datetime_functions::{datetime_component, datetime_series, datetime_to_string};
+use crate::type_inference::is_simple_string_type;
+use crate::type_promotion::{numeric_polars_dtype, numeric_rank};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use oxrdf::vocab::{rdf, xsd};
+use oxrdf::NamedNodeRef;
+use polars::datatypes::{DataType, TimeUnit};
+use polars::prelude::{
+    lit, BooleanChunked, Expr, Float64Chunked, GetOutput, Int32Chunked, Int64Chunked, IntoSeries,
+    LiteralValue, PolarsResult, Series, StringChunked, UInt64Chunked,
+};
+use representation::{literal_is_boolean, literal_is_numeric, RDFNodeType};
+
+pub const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const TIME_FORMAT: &str = "%H:%M:%S%.f";
+
+pub fn is_xsd_cast(nn: NamedNodeRef) -> bool {
+    matches!(
+        nn,
+        xsd::STRING
+            | xsd::BOOLEAN
+            | xsd::DOUBLE
+            | xsd::FLOAT
+            | xsd::DECIMAL
+            | xsd::INTEGER
+            | xsd::LONG
+            | xsd::INT
+            | xsd::SHORT
+            | xsd::BYTE
+            | xsd::UNSIGNED_LONG
+            | xsd::UNSIGNED_INT
+            | xsd::UNSIGNED_SHORT
+            | xsd::UNSIGNED_BYTE
+            | xsd::DATE_TIME
+            | xsd::DATE
+            | xsd::TIME
+    )
+}
+
+pub fn xsd_cast_dtype(nn: NamedNodeRef) -> DataType {
+    match nn {
+        xsd::STRING => DataType::String,
+        xsd::BOOLEAN => DataType::Boolean,
+        //Values without a timezone are kept naive, parsed values may be given a timezone or keep
+        //their lexical forms, see datetime_series
+        xsd::DATE_TIME => DataType::Datetime(TimeUnit::Nanoseconds, None),
+        xsd::DATE => DataType::Date,
+        xsd::TIME => DataType::Time,
+        _ => numeric_polars_dtype(nn),
+    }
+}
+
+//Follows the XPath casting table, casts that are not allowed give null
+pub fn xsd_cast(expr: Expr, from: &RDFNodeType, to: NamedNodeRef) -> Expr {
+    let dtype = xsd_cast_dtype(to);
+    let null = Expr::Literal(LiteralValue::Null).cast(dtype.clone());
+    let from_nn = match from {
+        RDFNodeType::IRI if to == xsd::STRING => return expr,
+        RDFNodeType::Literal(l) => l.as_ref(),
+        _ => return null,
+    };
+    if is_simple_string_type(from) {
+        return parse_strings(expr, to);
+    }
+    if from_nn == to {
+        return expr;
+    }
+    if to == xsd::STRING {
+        return match from_nn {
+            xsd::DATE_TIME => datetime_to_string(expr),
+            xsd::DATE => expr.dt().to_string(DATE_FORMAT),
+            xsd::TIME => expr.dt().to_string(TIME_FORMAT),
+            rdf::LANG_STRING => null,
+            _ => expr.cast(DataType::String),
+        };
+    }
+    if literal_is_numeric(from_nn) || literal_is_boolean(from_nn) {
+        if to == xsd::BOOLEAN {
+            //xsd:decimal is stored as a float
+            if numeric_rank(from_nn).is_some_and(|rank| rank >= 1) {
+                expr.clone().neq(lit(0.0)).and(expr.is_nan().not())
+            } else {
+                expr.cast(DataType::Int64).neq(lit(0))
+            }
+        } else if numeric_rank(to).is_some() {
+            //Out of range values and NaN or infinity cast to integers give null
+            expr.cast(dtype)
+        } else {
+            null
+        }
+    } else if from_nn == xsd::DATE_TIME {
+        //The date and time are those of the local time of each value
+        match to {
+            xsd::DATE => datetime_component(expr, |d| {
+                Utc.from_utc_datetime(d).timestamp().div_euclid(86_400)
+            })
+            .cast(DataType::Int32)
+            .cast(DataType::Date),
+            xsd::TIME => datetime_component(expr, |d| {
+                d.time()
+                    .signed_duration_since(NaiveTime::MIN)
+                    .num_nanoseconds()
+                    .unwrap_or_default()
+            })
+            .cast(DataType::Time),
+            _ => null,
+        }
+    } else if from_nn == xsd::DATE && to == xsd::DATE_TIME {
+        expr.cast(dtype)
+    } else {
+        null
+    }
+}
+
+fn parse_strings(expr: Expr, to: NamedNodeRef) -> Expr {
+    let dtype = xsd_cast_dtype(to);
+    let output = dtype.clone();
+    let to = to.into_owned();
+    expr.map(
+        move |s| {
+            let ca = s.str()?;
+            let parsed = match to.as_ref() {
+                xsd::STRING => s.clone(),
+                xsd::BOOLEAN => parse_each::<BooleanChunked, _, _>(ca, parse_boolean),
+                xsd::DOUBLE | xsd::FLOAT => parse_each::<Float64Chunked, _, _>(ca, parse_double),
+                xsd::DECIMAL => parse_each::<Float64Chunked, _, _>(ca, parse_decimal),
+                xsd::UNSIGNED_LONG => parse_each::<UInt64Chunked, _, _>(ca, |x| {
+                    parse_integer(x).and_then(|i| u64::try_from(i).ok())
+                }),
+                xsd::DATE_TIME => parse_datetimes(ca)?,
+                xsd::DATE => parse_each::<Int32Chunked, _, _>(ca, parse_date),
+                xsd::TIME => parse_each::<Int64Chunked, _, _>(ca, parse_time),
+                _ => parse_each::<Int64Chunked, _, _>(ca, |x| {
+                    parse_integer(x).and_then(|i| i64::try_from(i).ok())
+                }),
+            };
+            //Narrowing casts detect values out of range of the smaller integer types
+            let parsed = if to.as_ref() == xsd::DATE_TIME {
+                parsed
+            } else {
+                parsed.cast(&dtype)?
+            };
+            Ok(Some(parsed.with_name(s.name())))
+        },
+        GetOutput::from_type(output),
+    )
+}
+
+fn parse_each<C, T, F>(ca: &StringChunked, parse: F) -> Series
+where
+    C: FromIterator<Option<T>> + IntoSeries,
+    F: Fn(&str) -> Option<T>,
+{
+    ca.into_iter()
+        .map(|x| parse(x?.trim()))
+        .collect::<C>()
+        .into_series()
+}
+
+fn parse_boolean(s: &str) -> Option<bool> {
+    match s {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn without_sign(s: &str) -> &str {
+    s.strip_prefix(['+', '-']).unwrap_or(s)
+}
+
+fn parse_integer(s: &str) -> Option<i128> {
+    if is_digits(without_sign(s)) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn is_decimal(s: &str) -> bool {
+    match without_sign(s).split_once('.') {
+        Some((whole, fraction)) => {
+            (whole.is_empty() || is_digits(whole))
+                && (fraction.is_empty() || is_digits(fraction))
+                && !(whole.is_empty() && fraction.is_empty())
+        }
+        None => is_digits(without_sign(s)),
+    }
+}
+
+fn parse_decimal(s: &str) -> Option<f64> {
+    if is_decimal(s) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn parse_double(s: &str) -> Option<f64> {
+    match s {
+        "INF" | "+INF" => Some(f64::INFINITY),
+        "-INF" => Some(f64::NEG_INFINITY),
+        "NaN" => Some(f64::NAN),
+        _ => {
+            let (mantissa, exponent) = match s.split_once(['e', 'E']) {
+                Some((m, e)) => (m, Some(e)),
+                None => (s, None),
+            };
+            if is_decimal(mantissa) && exponent.is_none_or(|e| is_digits(without_sign(e))) {
+                s.parse().ok()
+            } else {
+                None
+            }
+        }
+    }
+}
+
+//Gives nanoseconds since the epoch, in UTC for values with a timezone and in local time
+//otherwise, and the offset of the timezone in seconds
+pub fn parse_datetime(s: &str) -> Option<(i64, Option<i32>)> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        Some((
+            dt.timestamp_nanos_opt()?,
+            Some(dt.offset().local_minus_utc()),
+        ))
+    } else {
+        let local = NaiveDateTime::parse_from_str(s, DATETIME_FORMAT).ok()?;
+        Some((Utc.from_utc_datetime(&local).timestamp_nanos_opt()?, None))
+    }
+}
+
+//Values keep their own offsets, or their lack of one, see datetime_series
+fn parse_datetimes(ca: &StringChunked) -> PolarsResult<Series> {
+    let parsed = ca.into_iter().map(|x| parse_datetime(x?.trim())).collect();
+    datetime_series(ca.name(), parsed)
+}
+
+//Dates and times may have a timezone, which is not kept
+fn without_timezone(s: &str) -> &str {
+    if let Some(s) = s.strip_suffix('Z') {
+        s
+    } else if s.len() > 6
+        && matches!(s.as_bytes()[s.len() - 6], b'+' | b'-')
+        && s.as_bytes()[s.len() - 3] == b':'
+    {
+        &s[..s.len() - 6]
+    } else {
+        s
+    }
+}
+
+//Gives days since the epoch
+fn parse_date(s: &str) -> Option<i32> {
+    let date = NaiveDate::parse_from_str(without_timezone(s), DATE_FORMAT).ok()?;
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)?;
+    i32::try_from(date.signed_duration_since(epoch).num_days()).ok()
+}
+
+//Gives nanoseconds since midnight
+fn parse_time(s: &str) -> Option<i64> {
+    let time = NaiveTime::parse_from_str(without_timezone(s), TIME_FORMAT).ok()?;
+    time.signed_duration_since(NaiveTime::MIN).num_nanoseconds()
+}
+
+//Lexical forms of datatypes without a native representation are kept as strings
+pub fn parse_lexical_form(value: Expr, datatype: NamedNodeRef) -> Expr {
+    if is_xsd_cast(datatype) {
+        parse_strings(value, datatype)
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::{col, df, DataFrame, IntoLazy};
+
+    fn cast_strings(values: &[&str], to: NamedNodeRef) -> Series {
+        let string = RDFNodeType::Literal(xsd::STRING.into_owned());
+        let df = df!("s" => values).unwrap();
+        let out = df
+            .lazy()
+            .select([xsd_cast(col("s"), &string, to)])
+            .collect()
+            .unwrap();
+        out.column("s").unwrap().clone()
+    }
+
+    fn cast_datetimes(values: &[&str]) -> Series {
+        cast_strings(values, xsd::DATE_TIME)
+    }
+
+    fn to_strings(s: Series, from: NamedNodeRef) -> Vec<Option<String>> {
+        let from = RDFNodeType::Literal(from.into_owned());
+        let df = DataFrame::new(vec![s]).unwrap();
+        let out = df
+            .lazy()
+            .select([xsd_cast(col("s"), &from, xsd::STRING)])
+            .collect()
+            .unwrap();
+        let strings = out.column("s").unwrap().str().unwrap().clone();
+        strings
+            .into_iter()
+            .map(|x| x.map(|x| x.to_string()))
+            .collect()
+    }
+
+    fn as_strings(s: Series) -> Vec<Option<String>> {
+        to_strings(s, xsd::DATE_TIME)
+    }
+
+    //Casts the values to the datatype and back to strings
+    fn round_trip(values: &[&str], datatype: NamedNodeRef) -> Vec<Option<String>> {
+        to_strings(cast_strings(values, datatype), datatype)
+    }
+
+    fn strings(values: &[Option<&str>]) -> Vec<Option<String>> {
+        values.iter().map(|x| x.map(|x| x.to_string())).collect()
+    }
+
+    fn floats(values: &[&str], datatype: NamedNodeRef) -> Vec<Option<f64>> {
+        let s = cast_strings(values, datatype)
+            .cast(&DataType::Float64)
+            .unwrap();
+        s.f64().unwrap().into_iter().collect()
+    }
+
+    #[test]
+    fn datetimes_without_timezone_stay_naive() {
+        let s = cast_datetimes(&["2020-01-01T00:00:00"]);
+        assert_eq!(s.dtype(), &DataType::Datetime(TimeUnit::Nanoseconds, None));
+        assert_eq!(as_strings(s), vec![Some("2020-01-01T00:00:00".to_string())]);
+    }
+
+    #[test]
+    fn datetimes_keep_a_shared_offset() {
+        let s = cast_datetimes(&["2020-01-01T00:00:00+02:00", "2020-06-01T12:30:00+02:00"]);
+        assert_eq!(
+            s.dtype(),
+            &DataType::Datetime(TimeUnit::Nanoseconds, Some("Etc/GMT-2".to_string()))
+        );
+        assert_eq!(
+            as_strings(s),
+            vec![
+                Some("2020-01-01T00:00:00+02:00".to_string()),
+                Some("2020-06-01T12:30:00+02:00".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn datetimes_with_different_offsets_keep_them() {
+        let values = ["2020-01-01T00:00:00+02:00", "2020-01-01T00:00:00Z"];
+        let s = cast_datetimes(&values);
+        assert_eq!(s.dtype(), &DataType::String);
+        assert_eq!(
+            as_strings(s),
+            vec![Some(values[0].to_string()), Some(values[1].to_string())]
+        );
+    }
+
+    #[test]
+    fn naive_and_zoned_datetimes_in_one_column_stay_apart() {
+        let values = ["2020-01-01T00:00:00", "2020-01-01T00:00:00Z"];
+        let s = cast_datetimes(&values);
+        assert_eq!(
+            as_strings(s),
+            vec![Some(values[0].to_string()), Some(values[1].to_string())]
+        );
+    }
+
+    #[test]
+    fn strings_to_booleans() {
+        let s = cast_strings(&["true", "1", " false ", "0", "yes", ""], xsd::BOOLEAN);
+        let values: Vec<_> = s.bool().unwrap().into_iter().collect();
+        assert_eq!(
+            values,
+            vec![Some(true), Some(true), Some(false), Some(false), None, None]
+        );
+    }
+
+    #[test]
+    fn strings_to_integers() {
+        assert_eq!(
+            round_trip(&["12", "+3", "-0", "1.0", "1e2", "abc"], xsd::INTEGER),
+            strings(&[Some("12"), Some("3"), Some("0"), None, None, None])
+        );
+    }
+
+    #[test]
+    fn strings_to_decimals_and_floats() {
+        assert_eq!(
+            floats(&["1.5", "-.5", "1.", ".", "1e2", "INF"], xsd::DECIMAL),
+            vec![Some(1.5), Some(-0.5), Some(1.0), None, None, None]
+        );
+        assert_eq!(
+            floats(&["1e2", "-INF", "2.5E-1", "e2", "1e", "inf"], xsd::FLOAT),
+            vec![
+                Some(100.0),
+                Some(f64::NEG_INFINITY),
+                Some(0.25),
+                None,
+                None,
+                None
+            ]
+        );
+        assert!(floats(&["NaN"], xsd::DOUBLE)[0].unwrap().is_nan());
+    }
+
+    #[test]
+    fn integers_out_of_range_are_unbound() {
+        assert_eq!(
+            round_trip(&["127", "128", "-128", "300"], xsd::BYTE),
+            strings(&[Some("127"), None, Some("-128"), None])
+        );
+        assert_eq!(
+            round_trip(&["32767", "40000"], xsd::SHORT),
+            strings(&[Some("32767"), None])
+        );
+        assert_eq!(
+            round_trip(&["9223372036854775808"], xsd::LONG),
+            strings(&[None])
+        );
+    }
+
+    #[test]
+    fn negative_unsigned_integers_are_unbound() {
+        assert_eq!(
+            round_trip(&["255", "256", "-1"], xsd::UNSIGNED_BYTE),
+            strings(&[Some("255"), None, None])
+        );
+        assert_eq!(
+            round_trip(&["-1", "-0"], xsd::UNSIGNED_INT),
+            strings(&[None, Some("0")])
+        );
+        assert_eq!(
+            round_trip(&["18446744073709551615", "-1"], xsd::UNSIGNED_LONG),
+            strings(&[Some("18446744073709551615"), None])
+        );
+    }
+
+    #[test]
+    fn strings_to_dates_and_times() {
+        assert_eq!(
+            round_trip(
+                &[
+                    "2020-02-29",
+                    "2020-02-30",
+                    "2020-01-01Z",
+                    "2020-01-01+02:00"
+                ],
+                xsd::DATE
+            ),
+            strings(&[
+                Some("2020-02-29"),
+                None,
+                Some("2020-01-01"),
+                Some("2020-01-01")
+            ])
+        );
+        assert_eq!(
+            round_trip(&["12:30:00", "24:00:01", "12:30:00.5Z"], xsd::TIME),
+            strings(&[Some("12:30:00"), None, Some("12:30:00.500")])
+        );
+    }
+
+    #[test]
+    fn invalid_datetimes_are_unbound() {
+        let s = cast_datetimes(&[
+            "2020-13-01T00:00:00",
+            "not a dateTime",
+            "2020-01-01T25:00:00Z",
+            "2020-01-01",
+        ]);
+        assert_eq!(as_strings(s), vec![None, None, None, None]);
+    }
+}
@@ -7,4 +7,12 @@ pub const SECONDS_AS_DATETIME: &str =
 
 pub const FLOOR_DATETIME_TO_SECONDS_INTERVAL: &str =
     "https://github.com/DataTreehouse/chrontext#FloorDateTimeToSecondsInterval";
+pub const FLOOR_DATETIME_TO_INTERVAL: &str =
+    "https://github.com/DataTreehouse/chrontext#FloorDateTimeToInterval";
+pub const CEIL_DATETIME_TO_INTERVAL: &str =
+    "https://github.com/DataTreehouse/chrontext#CeilDateTimeToInterval";
 pub const MODULUS: &str = "https://github.com/DataTreehouse/chrontext#modulus";
+pub const INTEGER_DIVIDE: &str = "https://github.com/DataTreehouse/chrontext#integerDivide";
+pub const ROUND_TO: &str = "https://github.com/DataTreehouse/chrontext#roundTo";
+
+pub const MATH_NAMESPACE: &str = "http://www.w3.org/2005/xpath-functions/math#";
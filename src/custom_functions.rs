@@ -0,0 +1,716 @@
+use crate::constants::{
+    CEIL_DATETIME_TO_INTERVAL, DATETIME_AS_NANOS, DATETIME_AS_SECONDS, FLOOR_DATETIME_TO_INTERVAL,
+    FLOOR_DATETIME_TO_SECONDS_INTERVAL, INTEGER_DIVIDE, MODULUS, NANOS_AS_DATETIME, ROUND_TO,
+    SECONDS_AS_DATETIME,
+};
+use crate::datetime_functions::{datetime_to_interval, is_interval, polars_datetimes};
+use crate::errors::QueryProcessingError;
+use crate::math_functions::{
+    integer_divide, numeric_mod, register_math_functions, round_half_to_even,
+};
+use crate::type_inference::{is_datetime_type, is_numeric_type, is_simple_string_type};
+use crate::type_promotion::{is_integer_type, promote_numeric_types};
+use chrono_tz::Tz;
+use oxrdf::vocab::xsd;
+use oxrdf::NamedNode;
+use polars::datatypes::{DataType, TimeUnit};
+use polars::prelude::{lit, Expr, LiteralValue};
+use representation::RDFNodeType;
+use spargebra::algebra::Expression;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::ops::{Div, Mul};
+use std::sync::Arc;
+
+pub trait CustomFunction: Send + Sync {
+    fn iri(&self) -> &str;
+    fn arity(&self) -> usize;
+    //Arguments that may follow the required ones
+    fn optional_arguments(&self) -> usize {
+        0
+    }
+    //None when the function is not defined for the argument types, which gives an unbound result
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType>;
+    //The SPARQL arguments give access to constants such as timezone names
+    fn expression(
+        &self,
+        args: Vec<Expr>,
+        arg_types: &[RDFNodeType],
+        sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError>;
+}
+
+#[derive(Clone)]
+pub struct CustomFunctionRegistry {
+    functions: HashMap<String, Arc<dyn CustomFunction>>,
+}
+
+impl CustomFunctionRegistry {
+    pub fn empty() -> CustomFunctionRegistry {
+        CustomFunctionRegistry {
+            functions: HashMap::new(),
+        }
+    }
+
+    //Replaces any function already registered with the same IRI
+    pub fn register(&mut self, function: Arc<dyn CustomFunction>) {
+        self.functions.insert(function.iri().to_string(), function);
+    }
+
+    pub fn get(&self, iri: &str) -> Option<&dyn CustomFunction> {
+        self.functions.get(iri).map(|f| f.as_ref())
+    }
+}
+
+impl Default for CustomFunctionRegistry {
+    fn default() -> Self {
+        let mut registry = CustomFunctionRegistry::empty();
+        registry.register(Arc::new(DateTimeAsNanos));
+        registry.register(Arc::new(DateTimeAsSeconds));
+        registry.register(Arc::new(NanosAsDateTime));
+        registry.register(Arc::new(SecondsAsDateTime));
+        registry.register(Arc::new(Modulus));
+        registry.register(Arc::new(IntegerDivide));
+        registry.register(Arc::new(RoundTo));
+        registry.register(Arc::new(FloorDateTimeToSecondsInterval));
+        registry.register(Arc::new(DateTimeToInterval { ceil: false }));
+        registry.register(Arc::new(DateTimeToInterval { ceil: true }));
+        register_math_functions(&mut registry);
+        registry
+    }
+}
+
+impl Debug for CustomFunctionRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.functions.keys()).finish()
+    }
+}
+
+fn all_types(arg_types: &[RDFNodeType], valid: &[fn(&RDFNodeType) -> bool]) -> bool {
+    arg_types.len() == valid.len() && arg_types.iter().zip(valid).all(|(t, v)| v(t))
+}
+
+struct DateTimeAsNanos;
+
+impl CustomFunction for DateTimeAsNanos {
+    fn iri(&self) -> &str {
+        DATETIME_AS_NANOS
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+        all_types(arg_types, &[is_datetime_type])
+            .then(|| RDFNodeType::Literal(xsd::INTEGER.into_owned()))
+    }
+
+    fn expression(
+        &self,
+        mut args: Vec<Expr>,
+        _arg_types: &[RDFNodeType],
+        _sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError> {
+        Ok(polars_datetimes(args.remove(0))
+            .dt()
+            .timestamp(TimeUnit::Nanoseconds))
+    }
+}
+
+struct DateTimeAsSeconds;
+
+impl CustomFunction for DateTimeAsSeconds {
+    fn iri(&self) -> &str {
+        DATETIME_AS_SECONDS
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+        all_types(arg_types, &[is_datetime_type])
+            .then(|| RDFNodeType::Literal(xsd::INTEGER.into_owned()))
+    }
+
+    fn expression(
+        &self,
+        mut args: Vec<Expr>,
+        _arg_types: &[RDFNodeType],
+        _sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError> {
+        Ok(polars_datetimes(args.remove(0))
+            .dt()
+            .timestamp(TimeUnit::Milliseconds)
+            .div(lit(1000)))
+    }
+}
+
+struct NanosAsDateTime;
+
+impl CustomFunction for NanosAsDateTime {
+    fn iri(&self) -> &str {
+        NANOS_AS_DATETIME
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+        all_types(arg_types, &[is_numeric_type])
+            .then(|| RDFNodeType::Literal(xsd::DATE_TIME.into_owned()))
+    }
+
+    fn expression(
+        &self,
+        mut args: Vec<Expr>,
+        _arg_types: &[RDFNodeType],
+        _sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError> {
+        Ok(args
+            .remove(0)
+            .cast(DataType::Datetime(TimeUnit::Nanoseconds, None)))
+    }
+}
+
+struct SecondsAsDateTime;
+
+impl CustomFunction for SecondsAsDateTime {
+    fn iri(&self) -> &str {
+        SECONDS_AS_DATETIME
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+        all_types(arg_types, &[is_numeric_type])
+            .then(|| RDFNodeType::Literal(xsd::DATE_TIME.into_owned()))
+    }
+
+    fn expression(
+        &self,
+        mut args: Vec<Expr>,
+        _arg_types: &[RDFNodeType],
+        _sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError> {
+        Ok(args
+            .remove(0)
+            .mul(Expr::Literal(LiteralValue::UInt64(1000)))
+            .cast(DataType::Datetime(TimeUnit::Milliseconds, None)))
+    }
+}
+
+struct Modulus;
+
+impl CustomFunction for Modulus {
+    fn iri(&self) -> &str {
+        MODULUS
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+        promoted_type(arg_types).map(RDFNodeType::Literal)
+    }
+
+    fn expression(
+        &self,
+        args: Vec<Expr>,
+        arg_types: &[RDFNodeType],
+        _sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError> {
+        //The argument types were checked by output_type
+        let promoted = promoted_type(arg_types).unwrap_or(xsd::DOUBLE.into_owned());
+        Ok(numeric_mod(
+            args[0].clone(),
+            args[1].clone(),
+            promoted.as_ref(),
+        ))
+    }
+}
+
+struct IntegerDivide;
+
+impl CustomFunction for IntegerDivide {
+    fn iri(&self) -> &str {
+        INTEGER_DIVIDE
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+        promoted_type(arg_types).map(|_| RDFNodeType::Literal(xsd::INTEGER.into_owned()))
+    }
+
+    fn expression(
+        &self,
+        args: Vec<Expr>,
+        arg_types: &[RDFNodeType],
+        _sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError> {
+        let integers = promoted_type(arg_types).is_some_and(|p| is_integer_type(p.as_ref()));
+        Ok(integer_divide(args[0].clone(), args[1].clone(), integers))
+    }
+}
+
+struct RoundTo;
+
+impl CustomFunction for RoundTo {
+    fn iri(&self) -> &str {
+        ROUND_TO
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    //Integer subtypes are promoted to xsd:integer, other numeric types are kept
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+        if let [RDFNodeType::Literal(v), RDFNodeType::Literal(p)] = arg_types {
+            if is_integer_type(p.as_ref()) {
+                return promote_numeric_types(v.as_ref(), v.as_ref(), false)
+                    .map(RDFNodeType::Literal);
+            }
+        }
+        None
+    }
+
+    fn expression(
+        &self,
+        args: Vec<Expr>,
+        arg_types: &[RDFNodeType],
+        _sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError> {
+        let value_type = match self.output_type(arg_types) {
+            Some(RDFNodeType::Literal(l)) => l,
+            _ => xsd::DOUBLE.into_owned(),
+        };
+        Ok(round_half_to_even(
+            args[0].clone(),
+            args[1].clone(),
+            value_type.as_ref(),
+        ))
+    }
+}
+
+//Follows op:numeric-mod and op:numeric-integer-divide in promoting the operands
+fn promoted_type(arg_types: &[RDFNodeType]) -> Option<NamedNode> {
+    if let [RDFNodeType::Literal(l), RDFNodeType::Literal(r)] = arg_types {
+        promote_numeric_types(l.as_ref(), r.as_ref(), false)
+    } else {
+        None
+    }
+}
+
+struct FloorDateTimeToSecondsInterval;
+
+impl CustomFunction for FloorDateTimeToSecondsInterval {
+    fn iri(&self) -> &str {
+        FLOOR_DATETIME_TO_SECONDS_INTERVAL
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+        all_types(arg_types, &[is_datetime_type, is_numeric_type])
+            .then(|| RDFNodeType::Literal(xsd::DATE_TIME.into_owned()))
+    }
+
+    fn expression(
+        &self,
+        args: Vec<Expr>,
+        _arg_types: &[RDFNodeType],
+        _sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError> {
+        let first_as_seconds = polars_datetimes(args[0].clone())
+            .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
+            .cast(DataType::UInt64)
+            .div(lit(1000));
+        Ok(
+            (first_as_seconds.clone() - (first_as_seconds % args[1].clone()))
+                .mul(Expr::Literal(LiteralValue::UInt64(1000)))
+                .cast(DataType::Datetime(TimeUnit::Milliseconds, None)),
+        )
+    }
+}
+
+//Floors or ceils a dateTime to calendar intervals such as 1mo, 1w or 1d, optionally in a
+//timezone and with buckets starting at an offset such as 6h. Values without a timezone are taken
+//to be in UTC, also when a timezone is given.
+struct DateTimeToInterval {
+    ceil: bool,
+}
+
+impl DateTimeToInterval {
+    fn constant_argument<'a>(
+        &self,
+        sparql_args: &'a [Expression],
+        i: usize,
+        valid: fn(&str) -> bool,
+    ) -> Result<Option<&'a str>, QueryProcessingError> {
+        match sparql_args.get(i) {
+            None => Ok(None),
+            Some(Expression::Literal(l)) if valid(l.value()) => Ok(Some(l.value())),
+            Some(e) => Err(QueryProcessingError::InvalidArgument(
+                self.iri().to_string(),
+                e.to_string(),
+            )),
+        }
+    }
+}
+
+impl CustomFunction for DateTimeToInterval {
+    fn iri(&self) -> &str {
+        if self.ceil {
+            CEIL_DATETIME_TO_INTERVAL
+        } else {
+            FLOOR_DATETIME_TO_INTERVAL
+        }
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn optional_arguments(&self) -> usize {
+        2
+    }
+
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+        matches!(arg_types.split_first(), Some((first, rest))
+            if is_datetime_type(first) && rest.iter().all(is_simple_string_type))
+        .then(|| RDFNodeType::Literal(xsd::DATE_TIME.into_owned()))
+    }
+
+    fn expression(
+        &self,
+        args: Vec<Expr>,
+        _arg_types: &[RDFNodeType],
+        sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError> {
+        //The interval may vary by row, the timezone and offset must be constant
+        if let Some(Expression::Literal(l)) = sparql_args.get(1) {
+            if !is_interval(l.value()) {
+                return Err(QueryProcessingError::InvalidArgument(
+                    self.iri().to_string(),
+                    l.to_string(),
+                ));
+            }
+        }
+        let timezone = self.constant_argument(sparql_args, 2, |tz| tz.parse::<Tz>().is_ok())?;
+        let offset = self.constant_argument(sparql_args, 3, is_interval)?;
+        Ok(datetime_to_interval(
+            args[0].clone(),
+            args[1].clone(),
+            timezone,
+            offset,
+            self.ceil,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::casts::xsd_cast;
+    use crate::expressions::{func_expression, literal};
+    use crate::settings::QuerySettings;
+    use oxrdf::{Literal, Variable};
+    use polars::prelude::{col, df, DataFrame, IntoLazy, Series};
+    use representation::query_context::{Context, PathEntry};
+    use representation::solution_mapping::SolutionMappings;
+    use spargebra::algebra::Function;
+
+    const DOUBLED: &str = "http://example.org/doubled";
+
+    struct Doubled;
+
+    impl CustomFunction for Doubled {
+        fn iri(&self) -> &str {
+            DOUBLED
+        }
+
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+            all_types(arg_types, &[is_numeric_type])
+                .then(|| RDFNodeType::Literal(xsd::INTEGER.into_owned()))
+        }
+
+        fn expression(
+            &self,
+            args: Vec<Expr>,
+            _arg_types: &[RDFNodeType],
+            _sparql_args: &[Expression],
+        ) -> Result<Expr, QueryProcessingError> {
+            Ok(args[0].clone().cast(DataType::Int64) * lit(2))
+        }
+    }
+
+    //Gives seven for any two arguments
+    struct Seven {
+        iri: &'static str,
+    }
+
+    impl CustomFunction for Seven {
+        fn iri(&self) -> &str {
+            self.iri
+        }
+
+        fn arity(&self) -> usize {
+            2
+        }
+
+        fn output_type(&self, _arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+            Some(RDFNodeType::Literal(xsd::INTEGER.into_owned()))
+        }
+
+        fn expression(
+            &self,
+            _args: Vec<Expr>,
+            _arg_types: &[RDFNodeType],
+            _sparql_args: &[Expression],
+        ) -> Result<Expr, QueryProcessingError> {
+            Ok(lit(7i64))
+        }
+    }
+
+    fn context(i: u16) -> Context {
+        Context::new().extension_with(PathEntry::FunctionCall(i))
+    }
+
+    //Evaluates a function of the columns in the contexts 0 and up into the context after them
+    fn evaluate(
+        sm: SolutionMappings,
+        iri: &str,
+        args: Vec<Expression>,
+        settings: &QuerySettings,
+    ) -> Result<(RDFNodeType, Series), QueryProcessingError> {
+        let args_contexts = (0..args.len()).map(|i| (i, context(i as u16))).collect();
+        let out = context(args.len() as u16);
+        let func = Function::Custom(NamedNode::new_unchecked(iri));
+        let sm = func_expression(sm, &func, &args, args_contexts, &out, settings)?;
+        let t = sm.rdf_node_types.get(out.as_str()).unwrap().clone();
+        let df = sm.mappings.collect().unwrap();
+        Ok((t, df.column(out.as_str()).unwrap().clone()))
+    }
+
+    //Evaluates a function of literals on a single row
+    fn call(
+        iri: &str,
+        args: Vec<Literal>,
+        settings: &QuerySettings,
+    ) -> Result<(RDFNodeType, Series), QueryProcessingError> {
+        let mut sm = SolutionMappings::new(df!("row" => [0i64]).unwrap().lazy(), HashMap::new());
+        for (i, arg) in args.iter().enumerate() {
+            sm = literal(sm, arg, &context(i as u16)).unwrap();
+        }
+        let args = args.into_iter().map(Expression::Literal).collect();
+        evaluate(sm, iri, args, settings)
+    }
+
+    fn int64_values(s: &Series) -> Vec<Option<i64>> {
+        s.cast(&DataType::Int64)
+            .unwrap()
+            .i64()
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn registered_functions_are_called_by_iri() {
+        let settings = QuerySettings::default().with_custom_function(Arc::new(Doubled));
+        let (t, s) = call(DOUBLED, vec![Literal::from(21i64)], &settings).unwrap();
+        assert_eq!(t, RDFNodeType::Literal(xsd::INTEGER.into_owned()));
+        assert_eq!(int64_values(&s), vec![Some(42)]);
+        //Functions are only known to the settings they are registered in
+        let result = call(
+            DOUBLED,
+            vec![Literal::from(21i64)],
+            &QuerySettings::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::UnsupportedFunction(..))
+        ));
+    }
+
+    #[test]
+    fn registering_a_default_iri_replaces_the_function() {
+        let args = vec![Literal::from(10i64), Literal::from(3i64)];
+        let (_, s) = call(MODULUS, args.clone(), &QuerySettings::default()).unwrap();
+        assert_eq!(int64_values(&s), vec![Some(1)]);
+        let settings =
+            QuerySettings::default().with_custom_function(Arc::new(Seven { iri: MODULUS }));
+        let (_, s) = call(MODULUS, args, &settings).unwrap();
+        assert_eq!(int64_values(&s), vec![Some(7)]);
+    }
+
+    #[test]
+    fn wrong_number_of_arguments_gives_the_arity_range() {
+        let settings = QuerySettings::default().with_custom_function(Arc::new(Doubled));
+        let cases = [
+            (FLOOR_DATETIME_TO_INTERVAL, 1, "2 to 4"),
+            (FLOOR_DATETIME_TO_INTERVAL, 5, "2 to 4"),
+            (DOUBLED, 2, "1"),
+        ];
+        for (iri, arg_count, range) in cases {
+            let args = (0..arg_count).map(|i| Literal::from(i as i64)).collect();
+            let result = call(iri, args, &settings);
+            assert!(matches!(
+                result,
+                Err(QueryProcessingError::WrongNumberOfArguments(_, expected, count))
+                    if expected == range && count == arg_count
+            ));
+        }
+    }
+
+    //A single row with the dateTime value in the context 0 and simple literals after it
+    fn with_datetime(value: &str, args: &[&str]) -> (SolutionMappings, Vec<Expression>) {
+        let string = RDFNodeType::Literal(xsd::STRING.into_owned());
+        let c = context(0);
+        let lexical_form = lit(Series::new(c.as_str(), [value]));
+        let mappings = df!("row" => [0i64])
+            .unwrap()
+            .lazy()
+            .with_column(xsd_cast(lexical_form, &string, xsd::DATE_TIME).alias(c.as_str()));
+        let types = HashMap::from([(
+            c.as_str().to_string(),
+            RDFNodeType::Literal(xsd::DATE_TIME.into_owned()),
+        )]);
+        let mut sm = SolutionMappings::new(mappings, types);
+        let mut sparql_args = vec![Expression::Variable(Variable::new_unchecked("t"))];
+        for (i, arg) in args.iter().enumerate() {
+            let arg = Literal::new_simple_literal(*arg);
+            sm = literal(sm, &arg, &context(i as u16 + 1)).unwrap();
+            sparql_args.push(Expression::Literal(arg));
+        }
+        (sm, sparql_args)
+    }
+
+    fn to_interval(
+        iri: &str,
+        value: &str,
+        args: &[&str],
+    ) -> Result<Option<String>, QueryProcessingError> {
+        let (sm, sparql_args) = with_datetime(value, args);
+        let (t, s) = evaluate(sm, iri, sparql_args, &QuerySettings::default())?;
+        assert_eq!(t, RDFNodeType::Literal(xsd::DATE_TIME.into_owned()));
+        let df = DataFrame::new(vec![s.with_name("s")]).unwrap();
+        let out = df
+            .lazy()
+            .select([xsd_cast(col("s"), &t, xsd::STRING)])
+            .collect()
+            .unwrap();
+        let strings = out.column("s").unwrap().str().unwrap().clone();
+        Ok(strings.get(0).map(|x| x.to_string()))
+    }
+
+    fn floor(value: &str, args: &[&str]) -> String {
+        to_interval(FLOOR_DATETIME_TO_INTERVAL, value, args)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn ceil(value: &str, args: &[&str]) -> String {
+        to_interval(CEIL_DATETIME_TO_INTERVAL, value, args)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn datetimes_are_floored_and_ceiled_to_calendar_intervals() {
+        let value = "2024-05-15T10:30:00Z";
+        assert_eq!(floor(value, &["1mo"]), "2024-05-01T00:00:00Z");
+        assert_eq!(ceil(value, &["1mo"]), "2024-06-01T00:00:00Z");
+        //Weeks start on Mondays
+        assert_eq!(floor(value, &["1w"]), "2024-05-13T00:00:00Z");
+        assert_eq!(ceil(value, &["1w"]), "2024-05-20T00:00:00Z");
+        assert_eq!(floor(value, &["1d"]), "2024-05-15T00:00:00Z");
+        assert_eq!(ceil(value, &["1d"]), "2024-05-16T00:00:00Z");
+    }
+
+    #[test]
+    fn ceil_of_a_value_on_a_boundary_is_the_value() {
+        let value = "2024-05-01T00:00:00Z";
+        assert_eq!(ceil(value, &["1mo"]), value);
+        assert_eq!(ceil(value, &["1d"]), value);
+        assert_eq!(floor(value, &["1mo"]), value);
+    }
+
+    #[test]
+    fn intervals_are_computed_in_the_local_time_of_the_timezone() {
+        //Summer time starts in Oslo on 2024-03-31, so that day is 23 hours long
+        let value = "2024-03-31T12:00:00Z";
+        let args = ["1d", "Europe/Oslo"];
+        assert_eq!(floor(value, &args), "2024-03-31T00:00:00+01:00");
+        assert_eq!(ceil(value, &args), "2024-04-01T00:00:00+02:00");
+        //Values without a timezone are taken to be in UTC
+        let naive = "2024-03-31T23:30:00";
+        assert_eq!(floor(naive, &args), "2024-04-01T00:00:00+02:00");
+        assert_eq!(floor(naive, &["1d"]), "2024-03-31T00:00:00");
+    }
+
+    #[test]
+    fn buckets_start_at_the_offset() {
+        let value = "2024-05-15T03:00:00Z";
+        assert_eq!(floor(value, &["1d", "UTC", "6h"]), "2024-05-14T06:00:00Z");
+        assert_eq!(ceil(value, &["1d", "UTC", "6h"]), "2024-05-15T06:00:00Z");
+        assert_eq!(
+            floor("2024-05-15T07:00:00Z", &["1d", "UTC", "6h"]),
+            "2024-05-15T06:00:00Z"
+        );
+    }
+
+    #[test]
+    fn timezones_and_offsets_must_be_valid_constants() {
+        let invalid = |iri: &str, args: &[&str]| {
+            matches!(
+                to_interval(iri, "2024-05-15T10:30:00Z", args),
+                Err(QueryProcessingError::InvalidArgument(..))
+            )
+        };
+        assert!(invalid(FLOOR_DATETIME_TO_INTERVAL, &["1x"]));
+        assert!(invalid(FLOOR_DATETIME_TO_INTERVAL, &["1d", "Mars/Olympus"]));
+        assert!(invalid(
+            CEIL_DATETIME_TO_INTERVAL,
+            &["1d", "UTC", "6 hours"]
+        ));
+        //Variables are not accepted for the timezone or the offset
+        for i in [2, 3] {
+            let (sm, mut sparql_args) = with_datetime("2024-05-15T10:30:00Z", &["1d", "UTC", "6h"]);
+            sparql_args[i] = Expression::Variable(Variable::new_unchecked("v"));
+            let result = evaluate(
+                sm,
+                FLOOR_DATETIME_TO_INTERVAL,
+                sparql_args,
+                &QuerySettings::default(),
+            );
+            assert!(matches!(
+                result,
+                Err(QueryProcessingError::InvalidArgument(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn functions_undefined_for_the_argument_types_are_unbound() {
+        let settings = QuerySettings::default().with_custom_function(Arc::new(Doubled));
+        let (t, s) = call(DOUBLED, vec![Literal::from("a")], &settings).unwrap();
+        assert_eq!(t, RDFNodeType::None);
+        assert_eq!(s.null_count(), 1);
+    }
+}
@@ -0,0 +1,791 @@
+use crate::broadcasting::broadcast_index;
+use crate::casts::{parse_datetime, DATETIME_FORMAT};
+use chrono::{FixedOffset, Months, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use oxrdf::vocab::xsd;
+use oxrdf::NamedNodeRef;
+use polars::datatypes::{DataType, TimeUnit};
+use polars::prelude::{
+    lit, map_multiple, when, BooleanChunked, Expr, GetOutput, Int64Chunked, IntoSeries,
+    LiteralValue, Operator, PolarsResult, Series, StringChunked,
+};
+use std::collections::HashSet;
+
+//Timezoned values are never more than 14 hours away from UTC
+const MAX_OFFSET_NANOS: i64 = 14 * 60 * 60 * 1_000_000_000;
+
+//Gives the UTC instant for timezoned values and the local time for values without a timezone
+fn timestamps(s: &Series, tu: TimeUnit) -> PolarsResult<(Int64Chunked, Option<String>)> {
+    let tz = if let DataType::Datetime(_, tz) = s.dtype() {
+        tz.clone()
+    } else {
+        None
+    };
+    let values = s
+        .cast(&DataType::Datetime(tu, tz.clone()))?
+        .cast(&DataType::Int64)?;
+    Ok((values.i64()?.clone(), tz))
+}
+
+fn offset_seconds(tz: &str, utc_micros: i64) -> Option<i32> {
+    let utc = Utc.timestamp_micros(utc_micros).single()?.naive_utc();
+    if let Ok(tz) = tz.parse::<Tz>() {
+        Some(tz.offset_from_utc_datetime(&utc).fix().local_minus_utc())
+    } else {
+        let offset: FixedOffset = tz.parse().ok()?;
+        Some(offset.local_minus_utc())
+    }
+}
+
+//A dateTime column is a Polars datetime when its values share a timezone that Polars can name,
+//otherwise it keeps the lexical forms of the values so that each value keeps its own offset, or
+//its lack of one. Gives nanoseconds since the epoch, in UTC for values with a timezone and in
+//local time otherwise, and the offset in seconds. Times are taken to be on the epoch day.
+pub fn datetime_values(s: &Series) -> PolarsResult<Vec<Option<(i64, Option<i32>)>>> {
+    match s.dtype() {
+        DataType::String => Ok(s
+            .str()?
+            .into_iter()
+            .map(|x| parse_datetime(x?.trim()))
+            .collect()),
+        DataType::Time => Ok(s
+            .cast(&DataType::Int64)?
+            .i64()?
+            .into_iter()
+            .map(|x| Some((x?, None)))
+            .collect()),
+        _ => {
+            let (values, tz) = timestamps(s, TimeUnit::Nanoseconds)?;
+            Ok(values
+                .into_iter()
+                .map(|x| {
+                    let x = x?;
+                    let offset = match &tz {
+                        Some(tz) => Some(offset_seconds(tz, x.div_euclid(1000))?),
+                        None => None,
+                    };
+                    Some((x, offset))
+                })
+                .collect())
+        }
+    }
+}
+
+//Builds a dateTime column from values as given by datetime_values
+pub fn datetime_series(
+    name: &str,
+    values: Vec<Option<(i64, Option<i32>)>>,
+) -> PolarsResult<Series> {
+    let offsets: HashSet<Option<i32>> = values.iter().flatten().map(|(_, o)| *o).collect();
+    let time_zone = match offsets.into_iter().collect::<Vec<_>>().as_slice() {
+        [] | [None] => Some(None),
+        [Some(offset)] => offset_time_zone(*offset).map(Some),
+        _ => None,
+    };
+    match time_zone {
+        Some(time_zone) => polars_datetime_series(name, values, time_zone),
+        None => Ok(lexical_datetime_series(name, values)),
+    }
+}
+
+//Results computed from dateTime values keep the representation of the column
+fn like_datetimes(s: &Series, values: Vec<Option<(i64, Option<i32>)>>) -> PolarsResult<Series> {
+    match s.dtype() {
+        DataType::Datetime(_, tz) => {
+            polars_datetime_series(s.name(), values, tz.clone())?.cast(s.dtype())
+        }
+        _ => Ok(lexical_datetime_series(s.name(), values)),
+    }
+}
+
+fn polars_datetime_series(
+    name: &str,
+    values: Vec<Option<(i64, Option<i32>)>>,
+    time_zone: Option<String>,
+) -> PolarsResult<Series> {
+    let nanos: Int64Chunked = values.into_iter().map(|x| x.map(|(n, _)| n)).collect();
+    nanos
+        .with_name(name)
+        .into_series()
+        .cast(&DataType::Datetime(TimeUnit::Nanoseconds, time_zone))
+}
+
+fn lexical_datetime_series(name: &str, values: Vec<Option<(i64, Option<i32>)>>) -> Series {
+    let strings: StringChunked = values
+        .into_iter()
+        .map(|x| {
+            let (nanos, offset) = x?;
+            format_datetime(nanos, offset)
+        })
+        .collect();
+    strings.with_name(name).into_series()
+}
+
+//Functions computed by Polars get the values of columns with several timezones in UTC, where
+//values without a timezone are taken to be in UTC
+pub fn polars_datetimes(expr: Expr) -> Expr {
+    let utc = DataType::Datetime(TimeUnit::Nanoseconds, Some("UTC".to_string()));
+    let output = utc.clone();
+    expr.map(
+        move |s| {
+            if s.dtype() != &DataType::String {
+                return Ok(Some(s));
+            }
+            let nanos: Int64Chunked = datetime_values(&s)?
+                .into_iter()
+                .map(|x| x.map(|(n, _)| n))
+                .collect();
+            Ok(Some(nanos.with_name(s.name()).into_series().cast(&utc)?))
+        },
+        GetOutput::map_dtype(move |dtype| {
+            if dtype == &DataType::String {
+                output.clone()
+            } else {
+                dtype.clone()
+            }
+        }),
+    )
+}
+
+fn local_datetime(nanos: i64, offset: Option<i32>) -> Option<NaiveDateTime> {
+    let local = nanos.checked_add(offset.unwrap_or(0) as i64 * 1_000_000_000)?;
+    Some(Utc.timestamp_nanos(local).naive_utc())
+}
+
+fn format_datetime(nanos: i64, offset: Option<i32>) -> Option<String> {
+    Some(format!(
+        "{}{}",
+        local_datetime(nanos, offset)?.format(DATETIME_FORMAT),
+        offset.map(format_offset).unwrap_or_default()
+    ))
+}
+
+//Gives a component of dateTime values in the local time of each value
+pub fn datetime_component(expr: Expr, component: fn(&NaiveDateTime) -> i64) -> Expr {
+    expr.map(
+        move |s| {
+            let out: Int64Chunked = datetime_values(&s)?
+                .into_iter()
+                .map(|x| {
+                    let (nanos, offset) = x?;
+                    Some(component(&local_datetime(nanos, offset)?))
+                })
+                .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::Int64),
+    )
+}
+
+pub fn timezone(expr: Expr) -> Expr {
+    expr.map(
+        |s| {
+            let out: Int64Chunked = datetime_values(&s)?
+                .into_iter()
+                .map(|x| Some(x?.1? as i64 * 1_000_000_000))
+                .collect();
+            Ok(Some(
+                out.with_name(s.name())
+                    .into_series()
+                    .cast(&DataType::Duration(TimeUnit::Nanoseconds))?,
+            ))
+        },
+        GetOutput::from_type(DataType::Duration(TimeUnit::Nanoseconds)),
+    )
+}
+
+pub fn tz(expr: Expr) -> Expr {
+    expr.map(
+        |s| {
+            let out: StringChunked = datetime_values(&s)?
+                .into_iter()
+                .map(|x| Some(x?.1.map(format_offset).unwrap_or_default()))
+                .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+//Gives the lexical form of dateTime values, with the offset when they have a timezone
+pub fn datetime_to_string(expr: Expr) -> Expr {
+    expr.map(
+        |s| {
+            let out: StringChunked = datetime_values(&s)?
+                .into_iter()
+                .map(|x| {
+                    let (nanos, offset) = x?;
+                    format_datetime(nanos, offset)
+                })
+                .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+//Polars only has named timezones, whole hour offsets are the Etc/GMT zones with reversed signs
+fn offset_time_zone(seconds: i32) -> Option<String> {
+    if seconds == 0 {
+        Some("UTC".to_string())
+    } else if seconds % 3600 == 0 {
+        let zone = format!("Etc/GMT{:+}", -seconds / 3600);
+        zone.parse::<Tz>().is_ok().then_some(zone)
+    } else {
+        None
+    }
+}
+
+fn format_offset(seconds: i32) -> String {
+    if seconds == 0 {
+        "Z".to_string()
+    } else {
+        let sign = if seconds < 0 { '-' } else { '+' };
+        let minutes = seconds.abs() / 60;
+        format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+    }
+}
+
+//Compares dateTime values following XSD: a value without a timezone is only comparable to a
+//timezoned value when the result is the same for every timezone it could have
+pub fn compare_datetimes(op: Operator, left: Expr, right: Expr) -> Expr {
+    map_multiple(
+        move |series| {
+            let left = datetime_values(&series[0])?;
+            let right = datetime_values(&series[1])?;
+            let len = left.len().max(right.len());
+            let out: BooleanChunked = (0..len)
+                .map(|i| {
+                    let (l, left_offset) = left[broadcast_index(left.len(), i)]?;
+                    let (r, right_offset) = right[broadcast_index(right.len(), i)]?;
+                    if left_offset.is_some() == right_offset.is_some() {
+                        compare(op, l, r)
+                    } else if op == Operator::Eq {
+                        if l.abs_diff(r) <= MAX_OFFSET_NANOS as u64 {
+                            None
+                        } else {
+                            Some(false)
+                        }
+                    } else {
+                        let earliest = compare(op, l.saturating_sub(MAX_OFFSET_NANOS), r);
+                        let latest = compare(op, l.saturating_add(MAX_OFFSET_NANOS), r);
+                        if earliest == latest {
+                            earliest
+                        } else {
+                            None
+                        }
+                    }
+                })
+                .collect();
+            Ok(Some(out.with_name(series[0].name()).into_series()))
+        },
+        [left, right],
+        GetOutput::from_type(DataType::Boolean),
+    )
+}
+
+fn compare(op: Operator, left: i64, right: i64) -> Option<bool> {
+    match op {
+        Operator::Eq => Some(left == right),
+        Operator::Lt => Some(left < right),
+        Operator::LtEq => Some(left <= right),
+        Operator::Gt => Some(left > right),
+        Operator::GtEq => Some(left >= right),
+        _ => None,
+    }
+}
+
+//Parses the lexical form of an xsd:duration into months and nanoseconds
+pub fn parse_duration(s: &str) -> Option<(i64, i64)> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let s = s.strip_prefix('P')?;
+    let (date_part, time_part) = match s.split_once('T') {
+        Some((d, t)) if !t.is_empty() => (d, Some(t)),
+        Some(_) => return None,
+        None => (s, None),
+    };
+    let mut months = 0i64;
+    let mut nanos = 0i64;
+    let mut components = 0;
+    for (value, designator) in duration_components(date_part)? {
+        let value: i64 = value.parse().ok()?;
+        match designator {
+            'Y' => months = months.checked_add(value.checked_mul(12)?)?,
+            'M' => months = months.checked_add(value)?,
+            'D' => nanos = nanos.checked_add(value.checked_mul(86_400_000_000_000)?)?,
+            _ => return None,
+        }
+        components += 1;
+    }
+    for (value, designator) in duration_components(time_part.unwrap_or(""))? {
+        let value = match designator {
+            'H' => value.parse::<i64>().ok()?.checked_mul(3_600_000_000_000)?,
+            'M' => value.parse::<i64>().ok()?.checked_mul(60_000_000_000)?,
+            'S' => {
+                let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+                let fraction = format!("{:0<9}", fraction);
+                if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                whole
+                    .parse::<i64>()
+                    .ok()?
+                    .checked_mul(1_000_000_000)?
+                    .checked_add(fraction.parse::<i64>().ok()?)?
+            }
+            _ => return None,
+        };
+        nanos = nanos.checked_add(value)?;
+        components += 1;
+    }
+    if components == 0 {
+        None
+    } else if negative {
+        Some((-months, -nanos))
+    } else {
+        Some((months, nanos))
+    }
+}
+
+//Durations are kept as one of the subtypes, which are totally ordered. Gives the subtype and the
+//number of months or nanoseconds, or None when the value is invalid or mixes months and days.
+pub fn duration_subtype(
+    datatype: NamedNodeRef,
+    value: &str,
+) -> Option<(NamedNodeRef<'static>, i64)> {
+    if !matches!(
+        datatype,
+        xsd::DURATION | xsd::DAY_TIME_DURATION | xsd::YEAR_MONTH_DURATION
+    ) {
+        return None;
+    }
+    match parse_duration(value)? {
+        (0, nanos) if datatype != xsd::YEAR_MONTH_DURATION => Some((xsd::DAY_TIME_DURATION, nanos)),
+        (months, 0) if datatype != xsd::DAY_TIME_DURATION => {
+            Some((xsd::YEAR_MONTH_DURATION, months))
+        }
+        _ => None,
+    }
+}
+
+//Splits for instance 1Y2M into (1, Y) and (2, M), designators may not repeat
+fn duration_components(s: &str) -> Option<Vec<(&str, char)>> {
+    let mut components = vec![];
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if !(c.is_ascii_digit() || c == '.') {
+            if i == start || components.iter().any(|(_, d)| *d == c) {
+                return None;
+            }
+            components.push((&s[start..i], c));
+            start = i + c.len_utf8();
+        }
+    }
+    if start == s.len() {
+        Some(components)
+    } else {
+        None
+    }
+}
+
+pub fn duration_polars_dtype(nn: NamedNodeRef) -> DataType {
+    if nn == xsd::YEAR_MONTH_DURATION {
+        DataType::Int64
+    } else {
+        DataType::Duration(TimeUnit::Nanoseconds)
+    }
+}
+
+//The difference between dateTime values as a duration in nanoseconds, unbound when only one of
+//the values has a timezone since the result would then depend on the implicit timezone
+fn datetime_difference(left: Expr, right: Expr) -> Expr {
+    map_multiple(
+        |series| {
+            let left = datetime_values(&series[0])?;
+            let right = datetime_values(&series[1])?;
+            let len = left.len().max(right.len());
+            let out: Int64Chunked = (0..len)
+                .map(|i| {
+                    let (l, left_offset) = left[broadcast_index(left.len(), i)]?;
+                    let (r, right_offset) = right[broadcast_index(right.len(), i)]?;
+                    if left_offset.is_some() == right_offset.is_some() {
+                        l.checked_sub(r)
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            Ok(Some(
+                out.with_name(series[0].name())
+                    .into_series()
+                    .cast(&DataType::Duration(TimeUnit::Nanoseconds))?,
+            ))
+        },
+        [left, right],
+        GetOutput::from_type(DataType::Duration(TimeUnit::Nanoseconds)),
+    )
+}
+
+//Adds nanoseconds or months to dateTime values, which keep their timezones. Months are added to
+//the local time, where the day is the last of the month when the month is shorter.
+fn shift_datetimes(datetimes: Expr, amounts: Expr, months: bool, subtract: bool) -> Expr {
+    map_multiple(
+        move |series| {
+            let values = datetime_values(&series[0])?;
+            let amounts = series[1].cast(&DataType::Int64)?;
+            let amounts = amounts.i64()?;
+            let zone = match series[0].dtype() {
+                DataType::Datetime(_, Some(tz)) => tz.parse::<Tz>().ok(),
+                _ => None,
+            };
+            let len = values.len().max(amounts.len());
+            let shifted = (0..len)
+                .map(|i| {
+                    let (nanos, offset) = values[broadcast_index(values.len(), i)]?;
+                    let amount = amounts.get(broadcast_index(amounts.len(), i))?;
+                    let amount = if subtract {
+                        amount.checked_neg()?
+                    } else {
+                        amount
+                    };
+                    if !months {
+                        return Some((nanos.checked_add(amount)?, offset));
+                    }
+                    let local = local_datetime(nanos, offset)?;
+                    let months = Months::new(u32::try_from(amount.unsigned_abs()).ok()?);
+                    let local = if amount < 0 {
+                        local.checked_sub_months(months)?
+                    } else {
+                        local.checked_add_months(months)?
+                    };
+                    match (offset, zone) {
+                        //Named timezones may have another offset at the new local time
+                        (Some(_), Some(zone)) => {
+                            let shifted = zone.from_local_datetime(&local).earliest()?;
+                            Some((
+                                shifted.timestamp_nanos_opt()?,
+                                Some(shifted.offset().fix().local_minus_utc()),
+                            ))
+                        }
+                        _ => {
+                            let local = Utc.from_utc_datetime(&local).timestamp_nanos_opt()?;
+                            let utc =
+                                local.checked_sub(offset.unwrap_or(0) as i64 * 1_000_000_000)?;
+                            Some((utc, offset))
+                        }
+                    }
+                })
+                .collect();
+            Ok(Some(like_datetimes(&series[0], shifted)?))
+        },
+        [datetimes, amounts],
+        GetOutput::same_type(),
+    )
+}
+
+//Arithmetic on xsd:dateTime and durations, where xsd:dayTimeDuration is a Polars duration in
+//nanoseconds and xsd:yearMonthDuration is a number of months
+pub fn temporal_arithmetic(
+    op: Operator,
+    left: Expr,
+    left_type: NamedNodeRef,
+    right: Expr,
+    right_type: NamedNodeRef,
+) -> Expr {
+    let nanos = DataType::Duration(TimeUnit::Nanoseconds);
+    match (op, left_type, right_type) {
+        (Operator::Plus | Operator::Minus, xsd::DATE_TIME, xsd::YEAR_MONTH_DURATION) => {
+            shift_datetimes(left, right, true, op == Operator::Minus)
+        }
+        (Operator::Plus | Operator::Minus, xsd::DATE_TIME, xsd::DAY_TIME_DURATION) => {
+            shift_datetimes(left, right.cast(nanos), false, op == Operator::Minus)
+        }
+        (Operator::Plus, xsd::YEAR_MONTH_DURATION | xsd::DAY_TIME_DURATION, xsd::DATE_TIME) => {
+            temporal_arithmetic(op, right, right_type, left, left_type)
+        }
+        (Operator::Minus, xsd::DATE_TIME, xsd::DATE_TIME) => datetime_difference(left, right),
+        (Operator::Divide, _, _) if left_type == right_type => {
+            //The ratio of two durations
+            let divisor = right.cast(DataType::Int64).cast(DataType::Float64);
+            when(divisor.clone().eq(lit(0.0)))
+                .then(Expr::Literal(LiteralValue::Null))
+                .otherwise(left.cast(DataType::Int64).cast(DataType::Float64) / divisor)
+        }
+        (Operator::Multiply, _, xsd::DAY_TIME_DURATION | xsd::YEAR_MONTH_DURATION) => {
+            temporal_arithmetic(op, right, right_type, left, left_type)
+        }
+        (Operator::Multiply | Operator::Divide, _, _) => {
+            //Durations are scaled and rounded to the nearest month or nanosecond
+            let factor = right.cast(DataType::Float64);
+            let scaled = Expr::BinaryExpr {
+                left: Box::new(left.cast(DataType::Int64).cast(DataType::Float64)),
+                op,
+                right: Box::new(factor.clone()),
+            }
+            .round(0)
+            .cast(DataType::Int64);
+            let scaled = if left_type == xsd::DAY_TIME_DURATION {
+                scaled.cast(nanos)
+            } else {
+                scaled
+            };
+            if op == Operator::Divide {
+                when(factor.eq(lit(0.0)))
+                    .then(Expr::Literal(LiteralValue::Null))
+                    .otherwise(scaled)
+            } else {
+                scaled
+            }
+        }
+        _ => Expr::BinaryExpr {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        },
+    }
+}
+
+//Extracts the year or month of xsd:gYear and xsd:gYearMonth values such as -0044 and 2024-05Z
+pub fn gregorian_component(expr: Expr, month: bool) -> Expr {
+    expr.map(
+        move |s| {
+            let out: Int64Chunked = s
+                .str()?
+                .into_iter()
+                .map(|x| {
+                    let x = x?;
+                    let (sign, unsigned) = match x.strip_prefix('-') {
+                        Some(rest) => (-1, rest),
+                        None => (1, x),
+                    };
+                    let year_end = unsigned
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(unsigned.len());
+                    if month {
+                        let month = unsigned[year_end..].strip_prefix('-')?.get(..2)?;
+                        month.parse().ok()
+                    } else {
+                        unsigned[..year_end].parse::<i64>().ok().map(|y| sign * y)
+                    }
+                })
+                .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::Int64),
+    )
+}
+
+//Intervals in the Polars duration language, for instance 1mo, 1w, 1d or 1h30m
+pub fn is_interval(s: &str) -> bool {
+    let mut rest = s.strip_prefix('-').unwrap_or(s);
+    if rest.is_empty() {
+        return false;
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return false;
+        }
+        rest = &rest[digits..];
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if !matches!(
+            &rest[..unit],
+            "ns" | "us" | "ms" | "s" | "m" | "h" | "d" | "w" | "mo" | "q" | "y"
+        ) {
+            return false;
+        }
+        rest = &rest[unit..];
+    }
+    true
+}
+
+//Buckets are computed in the local time of the timezone. Values without a timezone are taken to
+//be in UTC, both when converting them to the timezone and when no timezone is given.
+pub fn datetime_to_interval(
+    expr: Expr,
+    every: Expr,
+    timezone: Option<&str>,
+    offset: Option<&str>,
+    ceil: bool,
+) -> Expr {
+    let expr = polars_datetimes(expr);
+    let expr = if let Some(timezone) = timezone {
+        expr.cast(DataType::Datetime(
+            TimeUnit::Nanoseconds,
+            Some(timezone.to_string()),
+        ))
+    } else {
+        expr
+    };
+    //Buckets start at the offset rather than at the epoch
+    let (shifted, unshift) = if let Some(offset) = offset {
+        let negated = match offset.strip_prefix('-') {
+            Some(positive) => positive.to_string(),
+            None => format!("-{}", offset),
+        };
+        (expr.clone().dt().offset_by(lit(negated)), Some(offset))
+    } else {
+        (expr.clone(), None)
+    };
+    let floored = shifted.dt().truncate(every.clone(), "0ns".to_string());
+    let floored = if let Some(offset) = unshift {
+        floored.dt().offset_by(lit(offset))
+    } else {
+        floored
+    };
+    if ceil {
+        when(floored.clone().eq(expr))
+            .then(floored.clone())
+            .otherwise(floored.dt().offset_by(every))
+    } else {
+        floored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::casts::xsd_cast;
+    use chrono::Timelike;
+    use polars::prelude::{col, df, DataFrame, IntoLazy};
+    use representation::RDFNodeType;
+
+    fn datetimes(values: &[&str]) -> Series {
+        let string = RDFNodeType::Literal(xsd::STRING.into_owned());
+        let df = df!("s" => values).unwrap();
+        let out = df
+            .lazy()
+            .select([xsd_cast(col("s"), &string, xsd::DATE_TIME)])
+            .collect()
+            .unwrap();
+        out.column("s").unwrap().clone()
+    }
+
+    fn evaluate(columns: Vec<Series>, expr: Expr) -> Series {
+        let df = DataFrame::new(columns).unwrap();
+        let out = df.lazy().select([expr.alias("out")]).collect().unwrap();
+        out.column("out").unwrap().clone()
+    }
+
+    fn int64_values(s: &Series) -> Vec<Option<i64>> {
+        s.cast(&DataType::Int64)
+            .unwrap()
+            .i64()
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+
+    const HOUR: i64 = 3_600_000_000_000;
+
+    #[test]
+    fn tz_and_timezone_keep_the_offset_of_each_value() {
+        let values = [
+            "2020-01-01T00:00:00Z",
+            "2020-01-01T00:00:00+02:00",
+            "2020-01-01T00:00:00+05:30",
+            "2020-01-01T00:00:00-09:30",
+            "2020-01-01T00:00:00",
+        ];
+        let expected_tz = ["Z", "+02:00", "+05:30", "-09:30", ""];
+        let expected_timezone = [
+            Some(0),
+            Some(2 * HOUR),
+            Some(11 * HOUR / 2),
+            Some(-19 * HOUR / 2),
+            None,
+        ];
+        //Both in columns of their own and in a column mixing offsets
+        let mut columns: Vec<_> = values.iter().map(|v| datetimes(&[*v])).collect();
+        columns.push(datetimes(&values));
+        for (i, s) in columns.into_iter().enumerate() {
+            let range = if s.len() == 1 {
+                i..i + 1
+            } else {
+                0..values.len()
+            };
+            let tzs = evaluate(vec![s.clone()], tz(col("s")));
+            let tzs: Vec<_> = tzs.str().unwrap().into_iter().collect();
+            let expected: Vec<_> = expected_tz[range.clone()]
+                .iter()
+                .map(|t| Some(*t))
+                .collect();
+            assert_eq!(tzs, expected);
+            let timezones = evaluate(vec![s], timezone(col("s")));
+            assert_eq!(int64_values(&timezones), expected_timezone[range].to_vec());
+        }
+    }
+
+    #[test]
+    fn columns_mixing_offsets_keep_the_lexical_forms() {
+        let values = ["2020-01-01T10:15:00+05:30", "2020-01-01T10:15:00Z"];
+        let s = datetimes(&values);
+        assert_eq!(s.dtype(), &DataType::String);
+        let strings = evaluate(vec![s.clone()], datetime_to_string(col("s")));
+        let strings: Vec<_> = strings.str().unwrap().into_iter().collect();
+        assert_eq!(strings, vec![Some(values[0]), Some(values[1])]);
+        //Components are those of the local time of each value
+        let hours = evaluate(vec![s], datetime_component(col("s"), |d| d.hour() as i64));
+        assert_eq!(int64_values(&hours), vec![Some(10), Some(10)]);
+    }
+
+    #[test]
+    fn whole_hour_offsets_are_named_timezones() {
+        let s = datetimes(&["2020-01-01T00:00:00-05:00"]);
+        assert_eq!(
+            s.dtype(),
+            &DataType::Datetime(TimeUnit::Nanoseconds, Some("Etc/GMT+5".to_string()))
+        );
+        let s = datetimes(&["2020-01-01T00:00:00Z"]);
+        assert_eq!(
+            s.dtype(),
+            &DataType::Datetime(TimeUnit::Nanoseconds, Some("UTC".to_string()))
+        );
+    }
+
+    #[test]
+    fn naive_and_zoned_values_compare_outside_fourteen_hours() {
+        let naive = datetimes(&["2020-01-02T00:00:00"; 4]).with_name("l");
+        let zoned = datetimes(&[
+            "2020-01-02T13:59:00Z",
+            "2020-01-01T10:01:00Z",
+            "2020-01-02T14:01:00Z",
+            "2020-01-01T09:59:00Z",
+        ])
+        .with_name("r");
+        let compared = |op| {
+            let out = evaluate(
+                vec![naive.clone(), zoned.clone()],
+                compare_datetimes(op, col("l"), col("r")),
+            );
+            out.bool().unwrap().into_iter().collect::<Vec<_>>()
+        };
+        assert_eq!(
+            compared(Operator::Lt),
+            vec![None, None, Some(true), Some(false)]
+        );
+        assert_eq!(
+            compared(Operator::Eq),
+            vec![None, None, Some(false), Some(false)]
+        );
+    }
+
+    #[test]
+    fn values_in_mixed_columns_are_compared_by_their_own_timezones() {
+        let left = datetimes(&["2020-01-01T00:00:00", "2020-01-01T00:00:00+05:30"]);
+        let right = datetimes(&["2020-01-01T00:00:00", "2019-12-31T18:30:00Z"]);
+        let out = evaluate(
+            vec![left.with_name("l"), right.with_name("r")],
+            compare_datetimes(Operator::Eq, col("l"), col("r")),
+        );
+        let out: Vec<_> = out.bool().unwrap().into_iter().collect();
+        assert_eq!(out, vec![Some(true), Some(true)]);
+    }
+}
@@ -1,3 +1,4 @@
+use polars::prelude::PolarsError;
 use representation::RDFNodeType;
 use thiserror::Error;
 
@@ -7,4 +8,24 @@ pub enum QueryProcessingError {
     InconsistentDatatypes(String, RDFNodeType, RDFNodeType, String),
     #[error("Variable ?{} not found in context {}",.0, .1)]
     VariableNotFound(String, String),
+    #[error("Datatype of context {} not found", .0)]
+    DatatypeNotFound(String),
+    #[error("Unsupported function {}", .0)]
+    UnsupportedFunction(String),
+    #[error("Function {} expects {} arguments but got {}", .0, .1, .2)]
+    WrongNumberOfArguments(String, String, usize),
+    #[error("Invalid argument {} to function {}", .1, .0)]
+    InvalidArgument(String, String),
+    #[error("Missing context for argument {} of function {}", .1, .0)]
+    MissingArgumentContext(String, usize),
+    #[error("Unsupported binary operator {}", .0)]
+    UnsupportedBinaryOperator(String),
+    #[error("Unsupported expression {}", .0)]
+    UnsupportedExpression(String),
+    #[error("Invalid regular expression {}: {}", .0, .1)]
+    InvalidRegex(String, String),
+    #[error("Incompatible types {:?} for {} in context {}", .2, .1, .0)]
+    IncompatibleTypes(String, String, Vec<RDFNodeType>),
+    #[error("Polars error: {}", .0)]
+    PolarsError(#[from] PolarsError),
 }
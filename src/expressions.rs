@@ -1,27 +1,50 @@
-use crate::constants::{
-    DATETIME_AS_NANOS, DATETIME_AS_SECONDS, FLOOR_DATETIME_TO_SECONDS_INTERVAL, MODULUS,
-    NANOS_AS_DATETIME, SECONDS_AS_DATETIME,
+use crate::casts::{is_xsd_cast, parse_lexical_form, xsd_cast};
+use crate::datetime_functions::{
+    compare_datetimes, datetime_component, duration_polars_dtype, duration_subtype,
+    gregorian_component, temporal_arithmetic, timezone, tz,
 };
 use crate::errors::QueryProcessingError;
-use oxrdf::vocab::xsd;
+use crate::math_functions::round_half_up;
+use crate::settings::QuerySettings;
+use crate::string_functions::{
+    encode_for_uri, md5, regex_matches, regex_replace, regex_with_flags, sha1, sha256, sha384,
+    sha512, str_after, str_before, str_len, substr, xpath_replacement,
+};
+use crate::term_functions::{
+    call_site_uuid, hex_encode, random_doubles, random_uuids, resolve_iri,
+};
+use crate::type_inference::{
+    argument_types_valid, arithmetic_result_type, binary_operator_type, check_function_arity,
+    combinations, comparable_types, is_datetime_type, is_duration_type, is_float_type,
+    is_lang_string_type, is_numeric_type, splits_multitype_arguments, string_arguments_compatible,
+    unary_operator_type, unified_type,
+};
+use crate::type_promotion::{
+    is_integer_type, numeric_polars_dtype, numeric_rank, promote_all_numeric_types,
+    promote_numeric_types, temporal_operator_type,
+};
+use chrono::{Datelike, Timelike, Utc};
+use log::debug;
+use oxrdf::vocab::{rdf, xsd};
 use oxrdf::{Literal, NamedNode, NamedNodeRef, Variable};
 use polars::datatypes::{DataType, TimeUnit};
 use polars::frame::UniqueKeepStrategy;
 use polars::prelude::{
-    coalesce, col, concat_str, is_in, lit, Expr, IntoLazy, LazyFrame, LiteralValue, Operator,
-    Series,
+    as_struct, coalesce, col, concat_str, is_in, lit, when, Expr, IntoLazy, LazyFrame,
+    LiteralValue, Operator, Series,
 };
-use representation::query_context::Context;
+use representation::multitype::{base_col_name, convert_lf_col_to_multitype};
+use representation::query_context::{Context, PathEntry};
 use representation::solution_mapping::SolutionMappings;
 use representation::sparql_to_polars::{
     sparql_literal_to_polars_literal_value, sparql_named_node_to_polars_literal_value,
 };
 use representation::{
-    literal_is_boolean, literal_is_datetime, literal_is_numeric, literal_is_string, RDFNodeType,
+    literal_is_boolean, literal_is_numeric, literal_is_string, BaseRDFNodeType, RDFNodeType,
+    LANG_STRING_LANG_FIELD, LANG_STRING_VALUE_FIELD,
 };
 use spargebra::algebra::{Expression, Function};
 use std::collections::HashMap;
-use std::ops::{Div, Mul};
 
 pub fn named_node(
     mut solution_mappings: SolutionMappings,
@@ -42,13 +65,39 @@ pub fn literal(
     lit: &Literal,
     context: &Context,
 ) -> Result<SolutionMappings, QueryProcessingError> {
-    solution_mappings.mappings = solution_mappings.mappings.with_column(
-        Expr::Literal(sparql_literal_to_polars_literal_value(lit)).alias(context.as_str()),
-    );
-    solution_mappings.rdf_node_types.insert(
-        context.as_str().to_string(),
-        RDFNodeType::Literal(lit.datatype().into_owned()),
-    );
+    let mut t = RDFNodeType::Literal(lit.datatype().into_owned());
+    let expr = if let Some(language) = lit.language() {
+        as_struct(vec![
+            Expr::Literal(LiteralValue::String(lit.value().to_string()))
+                .alias(LANG_STRING_VALUE_FIELD),
+            Expr::Literal(LiteralValue::String(language.to_string())).alias(LANG_STRING_LANG_FIELD),
+        ])
+    } else if is_duration_type(&t) {
+        match duration_subtype(lit.datatype(), lit.value()) {
+            Some((xsd::DAY_TIME_DURATION, nanos)) => {
+                t = RDFNodeType::Literal(xsd::DAY_TIME_DURATION.into_owned());
+                Expr::Literal(LiteralValue::Duration(nanos, TimeUnit::Nanoseconds))
+            }
+            Some((subtype, months)) => {
+                t = RDFNodeType::Literal(subtype.into_owned());
+                Expr::Literal(LiteralValue::Int64(months))
+            }
+            //Durations mixing months and days have no totally ordered subtype, and are unbound
+            //like invalid durations
+            None => {
+                t = RDFNodeType::None;
+                Expr::Literal(LiteralValue::Null)
+            }
+        }
+    } else {
+        Expr::Literal(sparql_literal_to_polars_literal_value(lit))
+    };
+    solution_mappings.mappings = solution_mappings
+        .mappings
+        .with_column(expr.alias(context.as_str()));
+    solution_mappings
+        .rdf_node_types
+        .insert(context.as_str().to_string(), t);
     Ok(solution_mappings)
 }
 
@@ -80,69 +129,223 @@ pub fn binary_expression(
     right_context: &Context,
     context: &Context,
 ) -> Result<SolutionMappings, QueryProcessingError> {
-    solution_mappings.mappings = solution_mappings.mappings.with_column(
-        (Expr::BinaryExpr {
-            left: Box::new(col(left_context.as_str())),
+    let left_type = context_type(&solution_mappings, left_context)?.clone();
+    let right_type = context_type(&solution_mappings, right_context)?.clone();
+    let (expr, t) = if matches!(op, Operator::And | Operator::Or) {
+        //Operands are reduced to their effective boolean values, an error in one operand is
+        //only an error for the whole expression when the other operand does not decide it
+        let left = effective_boolean_value(col(left_context.as_str()), &left_type);
+        let right = effective_boolean_value(col(right_context.as_str()), &right_type);
+        let expr = if op == Operator::And {
+            left.and(right)
+        } else {
+            left.or(right)
+        };
+        (expr, RDFNodeType::Literal(xsd::BOOLEAN.into_owned()))
+    } else if matches!(left_type, RDFNodeType::MultiType(..))
+        || matches!(right_type, RDFNodeType::MultiType(..))
+    {
+        multitype_binary_expression(
             op,
-            right: Box::new(col(right_context.as_str())),
-        })
-        .alias(context.as_str()),
-    );
-    let t = match op {
-        Operator::LtEq
-        | Operator::GtEq
-        | Operator::Gt
-        | Operator::Lt
-        | Operator::And
-        | Operator::Eq
-        | Operator::Or => RDFNodeType::Literal(xsd::BOOLEAN.into_owned()),
+            col(left_context.as_str()),
+            &left_type,
+            col(right_context.as_str()),
+            &right_type,
+        )?
+    } else {
+        typed_binary_expression(
+            op,
+            col(left_context.as_str()),
+            &left_type,
+            col(right_context.as_str()),
+            &right_type,
+        )?
+    };
+    solution_mappings.mappings = solution_mappings
+        .mappings
+        .with_column(expr.alias(context.as_str()));
+    solution_mappings
+        .rdf_node_types
+        .insert(context.as_str().to_string(), t);
+    solution_mappings = drop_inner_contexts(solution_mappings, &vec![left_context, right_context]);
+    Ok(solution_mappings)
+}
+
+fn typed_binary_expression(
+    op: Operator,
+    left: Expr,
+    left_type: &RDFNodeType,
+    right: Expr,
+    right_type: &RDFNodeType,
+) -> Result<(Expr, RDFNodeType), QueryProcessingError> {
+    let expr = Expr::BinaryExpr {
+        left: Box::new(left.clone()),
+        op,
+        right: Box::new(right.clone()),
+    };
+    let out = match op {
+        Operator::And | Operator::Or => (expr, RDFNodeType::Literal(xsd::BOOLEAN.into_owned())),
+        Operator::LtEq | Operator::GtEq | Operator::Gt | Operator::Lt | Operator::Eq => {
+            let expr = if is_datetime_type(left_type) && is_datetime_type(right_type) {
+                compare_datetimes(op, left, right)
+            } else if comparable_types(op, left_type, right_type) {
+                expr
+            } else if op == Operator::Eq
+                && left_type != &RDFNodeType::None
+                && right_type != &RDFNodeType::None
+                && (!matches!(left_type, RDFNodeType::Literal(..))
+                    || !matches!(right_type, RDFNodeType::Literal(..)))
+            {
+                //Distinct kinds of terms are never RDF-term-equal
+                Expr::Literal(LiteralValue::Boolean(false))
+            } else {
+                Expr::Literal(LiteralValue::Null).cast(DataType::Boolean)
+            };
+            (expr, RDFNodeType::Literal(xsd::BOOLEAN.into_owned()))
+        }
         Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide => {
-            let left_type = solution_mappings
-                .rdf_node_types
-                .get(left_context.as_str())
-                .unwrap();
-            let right_type = solution_mappings
-                .rdf_node_types
-                .get(right_context.as_str())
-                .unwrap();
-            let div_int = if op == Operator::Divide {
-                if let RDFNodeType::Literal(right_lit) = right_type {
-                    matches!(
-                        right_lit.as_ref(),
-                        xsd::INT
-                            | xsd::LONG
-                            | xsd::INTEGER
-                            | xsd::BYTE
-                            | xsd::SHORT
-                            | xsd::UNSIGNED_INT
-                            | xsd::UNSIGNED_LONG
-                            | xsd::UNSIGNED_BYTE
-                            | xsd::UNSIGNED_SHORT
-                    )
-                } else {
-                    false
-                }
+            let (promoted, temporal) = if let (RDFNodeType::Literal(l1), RDFNodeType::Literal(l2)) =
+                (left_type, right_type)
+            {
+                (
+                    promote_numeric_types(l1.as_ref(), l2.as_ref(), op == Operator::Divide),
+                    temporal_operator_type(op, l1.as_ref(), l2.as_ref()).map(|t| (l1, l2, t)),
+                )
             } else {
-                false
+                (None, None)
             };
-
-            if div_int {
-                RDFNodeType::Literal(xsd::DOUBLE.into_owned())
-                //TODO: Fix,
+            if let Some((l1, l2, t)) = temporal {
+                (
+                    temporal_arithmetic(op, left, l1.as_ref(), right, l2.as_ref()),
+                    RDFNodeType::Literal(t),
+                )
+            } else if let Some(promoted) = promoted {
+                let dtype = numeric_polars_dtype(promoted.as_ref());
+                let expr = Expr::BinaryExpr {
+                    left: Box::new(left.cast(dtype.clone())),
+                    op,
+                    right: Box::new(right.clone().cast(dtype)),
+                };
+                let expr = if op == Operator::Divide
+                    && !matches!(promoted.as_ref(), xsd::FLOAT | xsd::DOUBLE)
+                {
+                    //Division by zero is only defined for xsd:float and xsd:double
+                    when(right.eq(lit(0)))
+                        .then(Expr::Literal(LiteralValue::Null))
+                        .otherwise(expr)
+                } else {
+                    expr
+                };
+                (expr, RDFNodeType::Literal(promoted))
             } else {
-                left_type.clone()
+                (Expr::Literal(LiteralValue::Null), RDFNodeType::None)
             }
         }
         _ => {
-            panic!()
+            return Err(QueryProcessingError::UnsupportedBinaryOperator(format!(
+                "{:?}",
+                op
+            )))
         }
     };
+    Ok(out)
+}
 
-    solution_mappings
-        .rdf_node_types
-        .insert(context.as_str().to_string(), t);
-    solution_mappings = drop_inner_contexts(solution_mappings, &vec![left_context, right_context]);
-    Ok(solution_mappings)
+fn multitype_binary_expression(
+    op: Operator,
+    left: Expr,
+    left_type: &RDFNodeType,
+    right: Expr,
+    right_type: &RDFNodeType,
+) -> Result<(Expr, RDFNodeType), QueryProcessingError> {
+    //Evaluate each combination of base types, at most one of which is present in a row
+    let mut parts = vec![];
+    for (lt, le, lp) in typed_parts(left, left_type) {
+        for (rt, re, rp) in typed_parts(right.clone(), right_type) {
+            let (e, t) = typed_binary_expression(op, le.clone(), &lt, re, &rt)?;
+            parts.push((lp.clone().and(rp), e, t));
+        }
+    }
+    let out = match op {
+        Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide => {
+            let parts: Vec<_> = parts
+                .into_iter()
+                .filter_map(|(p, e, t)| match t {
+                    RDFNodeType::Literal(l) => Some((p, e, l)),
+                    _ => None,
+                })
+                .collect();
+            let types: Vec<_> = parts.iter().map(|(_, _, l)| l.clone()).collect();
+            let promoted = promote_all_numeric_types(types.iter().map(|t| t.as_ref()));
+            //Numeric results are cast to a common type, temporal results keep their own types
+            let parts: Vec<_> = parts
+                .into_iter()
+                .map(|(p, e, l)| match &promoted {
+                    Some(promoted) if numeric_rank(l.as_ref()).is_some() => {
+                        let e = e.cast(numeric_polars_dtype(promoted.as_ref()));
+                        (p, e, BaseRDFNodeType::Literal(promoted.clone()))
+                    }
+                    _ => (p, e, BaseRDFNodeType::Literal(l)),
+                })
+                .collect();
+            let select = |bt: Option<&BaseRDFNodeType>| {
+                let exprs: Vec<_> = parts
+                    .iter()
+                    .filter(|(_, _, t)| bt.is_none_or(|bt| t == bt))
+                    .map(|(p, e, _)| {
+                        when(p.clone())
+                            .then(e.clone())
+                            .otherwise(Expr::Literal(LiteralValue::Null))
+                    })
+                    .collect();
+                coalesce(exprs.as_slice())
+            };
+            match arithmetic_result_type(&types) {
+                Some(RDFNodeType::MultiType(target_types)) => {
+                    let fields = target_types
+                        .iter()
+                        .map(|bt| select(Some(bt)).alias(&base_col_name(bt)))
+                        .collect();
+                    (as_struct(fields), RDFNodeType::MultiType(target_types))
+                }
+                Some(t) => (select(None), t),
+                None => (Expr::Literal(LiteralValue::Null), RDFNodeType::None),
+            }
+        }
+        _ => {
+            let exprs: Vec<_> = parts
+                .into_iter()
+                .map(|(p, e, _)| when(p).then(e).otherwise(Expr::Literal(LiteralValue::Null)))
+                .collect();
+            (
+                coalesce(exprs.as_slice()),
+                RDFNodeType::Literal(xsd::BOOLEAN.into_owned()),
+            )
+        }
+    };
+    Ok(out)
+}
+
+//Splits a possibly multi typed expression into typed parts and their presence
+fn typed_parts(expr: Expr, t: &RDFNodeType) -> Vec<(RDFNodeType, Expr, Expr)> {
+    if let RDFNodeType::MultiType(types) = t {
+        let mut parts = vec![];
+        for bt in types {
+            let field = multitype_field(expr.clone(), bt);
+            let part = if bt.is_lang_string() {
+                as_struct(vec![
+                    field.clone(),
+                    expr.clone().struct_().field_by_name(LANG_STRING_LANG_FIELD),
+                ])
+            } else {
+                field.clone()
+            };
+            parts.push((bt.as_rdf_node_type(), part, field.is_not_null()));
+        }
+        parts
+    } else {
+        vec![(t.clone(), expr.clone(), expr.is_not_null())]
+    }
 }
 
 pub fn unary_plus(
@@ -150,16 +353,18 @@ pub fn unary_plus(
     plus_context: &Context,
     context: &Context,
 ) -> Result<SolutionMappings, QueryProcessingError> {
+    let existing_type = context_type(&solution_mappings, plus_context)?.clone();
+    let (expr, t) = if let Some(t) = unary_operator_type(&existing_type, false) {
+        (col(plus_context.as_str()), t)
+    } else {
+        (Expr::Literal(LiteralValue::Null), RDFNodeType::None)
+    };
     solution_mappings.mappings = solution_mappings
         .mappings
-        .with_column(col(plus_context.as_str()).alias(context.as_str()));
-    let existing_type = solution_mappings
-        .rdf_node_types
-        .get(plus_context.as_str())
-        .unwrap();
+        .with_column(expr.alias(context.as_str()));
     solution_mappings
         .rdf_node_types
-        .insert(context.as_str().to_string(), existing_type.clone());
+        .insert(context.as_str().to_string(), t);
     solution_mappings = drop_inner_contexts(solution_mappings, &vec![plus_context]);
     Ok(solution_mappings)
 }
@@ -169,23 +374,31 @@ pub fn unary_minus(
     minus_context: &Context,
     context: &Context,
 ) -> Result<SolutionMappings, QueryProcessingError> {
-    solution_mappings.mappings = solution_mappings //TODO: This is probably wrong
+    let existing_type = context_type(&solution_mappings, minus_context)?.clone();
+    let (expr, t) = match unary_operator_type(&existing_type, true) {
+        Some(RDFNodeType::Literal(l)) => {
+            let dtype = if is_duration_type(&existing_type) {
+                duration_polars_dtype(l.as_ref())
+            } else {
+                numeric_polars_dtype(l.as_ref())
+            };
+            (
+                Expr::BinaryExpr {
+                    left: Box::new(Expr::Literal(LiteralValue::Int32(0)).cast(dtype.clone())),
+                    op: Operator::Minus,
+                    right: Box::new(col(minus_context.as_str()).cast(dtype)),
+                },
+                RDFNodeType::Literal(l),
+            )
+        }
+        _ => (Expr::Literal(LiteralValue::Null), RDFNodeType::None),
+    };
+    solution_mappings.mappings = solution_mappings
         .mappings
-        .with_column(
-            (Expr::BinaryExpr {
-                left: Box::new(Expr::Literal(LiteralValue::Int32(0))),
-                op: Operator::Minus,
-                right: Box::new(col(minus_context.as_str())),
-            })
-            .alias(context.as_str()),
-        );
-    let existing_type = solution_mappings
-        .rdf_node_types
-        .get(minus_context.as_str())
-        .unwrap();
+        .with_column(expr.alias(context.as_str()));
     solution_mappings
         .rdf_node_types
-        .insert(context.as_str().to_string(), existing_type.clone());
+        .insert(context.as_str().to_string(), t);
     solution_mappings = drop_inner_contexts(solution_mappings, &vec![minus_context]);
     Ok(solution_mappings)
 }
@@ -195,9 +408,11 @@ pub fn not_expression(
     not_context: &Context,
     context: &Context,
 ) -> Result<SolutionMappings, QueryProcessingError> {
+    let not_type = context_type(&solution_mappings, not_context)?;
+    let expr = effective_boolean_value(col(not_context.as_str()), not_type).not();
     solution_mappings.mappings = solution_mappings
         .mappings
-        .with_column(col(not_context.as_str()).not().alias(context.as_str()));
+        .with_column(expr.alias(context.as_str()));
     solution_mappings.rdf_node_types.insert(
         context.as_str().to_string(),
         RDFNodeType::Literal(xsd::BOOLEAN.into_owned()),
@@ -229,22 +444,50 @@ pub fn if_expression(
     right_context: &Context,
     context: &Context,
 ) -> Result<SolutionMappings, QueryProcessingError> {
-    solution_mappings.mappings = solution_mappings.mappings.with_column(
-        (Expr::Ternary {
-            predicate: Box::new(col(left_context.as_str())),
-            truthy: Box::new(col(middle_context.as_str())),
-            falsy: Box::new(col(right_context.as_str())),
-        })
-        .alias(context.as_str()),
+    let predicate = effective_boolean_value(
+        col(left_context.as_str()),
+        context_type(&solution_mappings, left_context)?,
     );
-    //Todo: generalize..
-    let existing_type = solution_mappings
-        .rdf_node_types
-        .get(middle_context.as_str())
-        .unwrap();
+    let middle_type = context_type(&solution_mappings, middle_context)?.clone();
+    let right_type = context_type(&solution_mappings, right_context)?.clone();
+    let target_type = unified_type(&[middle_type.clone(), right_type.clone()]);
+    let expr = if let RDFNodeType::MultiType(target_types) = &target_type {
+        let (mappings, branch_types) = branches_to_multitype(
+            solution_mappings.mappings,
+            vec![(middle_context, &middle_type), (right_context, &right_type)],
+        );
+        solution_mappings.mappings = mappings;
+        let mut branch_types = branch_types.into_iter();
+        let choices = vec![
+            (
+                predicate.clone(),
+                col(middle_context.as_str()),
+                branch_types.next().unwrap(),
+            ),
+            (
+                predicate.not(),
+                col(right_context.as_str()),
+                branch_types.next().unwrap(),
+            ),
+        ];
+        //A null condition selects neither branch
+        select_multitype(choices, target_types)
+    } else {
+        //An error in the condition is an error for the whole expression
+        when(predicate.clone().is_null())
+            .then(Expr::Literal(LiteralValue::Null))
+            .otherwise(Expr::Ternary {
+                predicate: Box::new(predicate),
+                truthy: Box::new(col(middle_context.as_str())),
+                falsy: Box::new(col(right_context.as_str())),
+            })
+    };
+    solution_mappings.mappings = solution_mappings
+        .mappings
+        .with_column(expr.alias(context.as_str()));
     solution_mappings
         .rdf_node_types
-        .insert(context.as_str().to_string(), existing_type.clone());
+        .insert(context.as_str().to_string(), target_type);
     solution_mappings = drop_inner_contexts(
         solution_mappings,
         &vec![left_context, middle_context, right_context],
@@ -257,26 +500,107 @@ pub fn coalesce_expression(
     inner_contexts: Vec<Context>,
     context: &Context,
 ) -> Result<SolutionMappings, QueryProcessingError> {
-    let mut coal_exprs = vec![];
+    let mut inner_types = vec![];
     for c in &inner_contexts {
-        coal_exprs.push(col(c.as_str()));
+        inner_types.push(context_type(&solution_mappings, c)?.clone());
     }
-
+    let target_type = unified_type(&inner_types);
+    let expr = if let RDFNodeType::MultiType(target_types) = &target_type {
+        let (mappings, branch_types) = branches_to_multitype(
+            solution_mappings.mappings,
+            inner_contexts.iter().zip(inner_types.iter()).collect(),
+        );
+        solution_mappings.mappings = mappings;
+        let choices = inner_contexts
+            .iter()
+            .zip(branch_types)
+            .map(|(c, types)| {
+                (
+                    multitype_present(col(c.as_str()), &types),
+                    col(c.as_str()),
+                    types,
+                )
+            })
+            .collect();
+        select_multitype(choices, target_types)
+    } else {
+        let mut coal_exprs = vec![];
+        for c in &inner_contexts {
+            coal_exprs.push(col(c.as_str()));
+        }
+        coalesce(coal_exprs.as_slice())
+    };
     solution_mappings.mappings = solution_mappings
         .mappings
-        .with_column(coalesce(coal_exprs.as_slice()).alias(context.as_str()));
-    //TODO: generalize
-    let existing_type = solution_mappings
-        .rdf_node_types
-        .get(inner_contexts.first().unwrap().as_str())
-        .unwrap();
+        .with_column(expr.alias(context.as_str()));
     solution_mappings
         .rdf_node_types
-        .insert(context.as_str().to_string(), existing_type.clone());
+        .insert(context.as_str().to_string(), target_type);
     solution_mappings = drop_inner_contexts(solution_mappings, &inner_contexts.iter().collect());
     Ok(solution_mappings)
 }
 
+fn branches_to_multitype(
+    mut mappings: LazyFrame,
+    branches: Vec<(&Context, &RDFNodeType)>,
+) -> (LazyFrame, Vec<Vec<BaseRDFNodeType>>) {
+    let mut branch_types = vec![];
+    for (c, t) in branches {
+        let types = match t {
+            RDFNodeType::MultiType(types) => types.clone(),
+            RDFNodeType::None => vec![],
+            _ => {
+                mappings = convert_lf_col_to_multitype(mappings, &c.as_str().to_string(), t);
+                vec![BaseRDFNodeType::from_rdf_node_type(t)]
+            }
+        };
+        branch_types.push(types);
+    }
+    (mappings, branch_types)
+}
+
+//Builds a multitype column from the first branch with a true condition
+fn select_multitype(
+    choices: Vec<(Expr, Expr, Vec<BaseRDFNodeType>)>,
+    target_types: &[BaseRDFNodeType],
+) -> Expr {
+    let mut fields = vec![];
+    for bt in target_types {
+        for name in multitype_field_names(bt) {
+            let mut field_expr = Expr::Literal(LiteralValue::Null);
+            for (condition, expr, types) in choices.iter().rev() {
+                let field = if types.contains(bt) {
+                    expr.clone().struct_().field_by_name(&name)
+                } else {
+                    Expr::Literal(LiteralValue::Null)
+                };
+                field_expr = when(condition.clone()).then(field).otherwise(field_expr);
+            }
+            fields.push(field_expr.alias(&name));
+        }
+    }
+    as_struct(fields)
+}
+
+fn multitype_present(expr: Expr, types: &[BaseRDFNodeType]) -> Expr {
+    let mut present = Expr::Literal(LiteralValue::Boolean(false));
+    for bt in types {
+        present = present.or(multitype_field(expr.clone(), bt).is_not_null());
+    }
+    present
+}
+
+fn multitype_field_names(bt: &BaseRDFNodeType) -> Vec<String> {
+    if bt.is_lang_string() {
+        vec![
+            LANG_STRING_VALUE_FIELD.to_string(),
+            LANG_STRING_LANG_FIELD.to_string(),
+        ]
+    } else {
+        vec![base_col_name(bt)]
+    }
+}
+
 pub fn exists(
     solution_mappings: SolutionMappings,
     exists_lf: LazyFrame,
@@ -287,22 +611,18 @@ pub fn exists(
         mappings,
         rdf_node_types,
     } = solution_mappings;
-    let mut df = mappings.collect().unwrap();
+    let mut df = mappings.collect()?;
     let exists_df = exists_lf
         .select([col(exists_context.as_str())])
         .unique(None, UniqueKeepStrategy::First)
-        .collect()
-        .expect("Collect lazy exists error");
-    let mut ser = Series::from(
-        is_in(
-            //TODO: Fix - this can now work in lazy
-            df.column(exists_context.as_str()).unwrap(),
-            exists_df.column(exists_context.as_str()).unwrap(),
-        )
-        .unwrap(),
-    );
+        .collect()?;
+    let mut ser = Series::from(is_in(
+        //TODO: Fix - this can now work in lazy
+        df.column(exists_context.as_str())?,
+        exists_df.column(exists_context.as_str())?,
+    )?);
     ser.rename(context.as_str());
-    df.with_column(ser).unwrap();
+    df.with_column(ser)?;
     let mut solution_mappings = SolutionMappings::new(df.lazy(), rdf_node_types);
     solution_mappings = drop_inner_contexts(solution_mappings, &vec![exists_context]);
     Ok(solution_mappings)
@@ -314,109 +634,120 @@ pub fn func_expression(
     args: &Vec<Expression>,
     args_contexts: HashMap<usize, Context>,
     context: &Context,
+    settings: &QuerySettings,
 ) -> Result<SolutionMappings, QueryProcessingError> {
-    match func {
-        Function::Year => {
-            assert_eq!(args.len(), 1);
-            let first_context = args_contexts.get(&0).unwrap();
-            solution_mappings.mappings = solution_mappings.mappings.with_column(
-                col(first_context.as_str())
-                    .dt()
-                    .year()
-                    .alias(context.as_str()),
-            );
-            solution_mappings.rdf_node_types.insert(
-                context.as_str().to_string(),
-                RDFNodeType::Literal(xsd::UNSIGNED_INT.into_owned()),
-            );
-        }
-        Function::Month => {
-            assert_eq!(args.len(), 1);
-            let first_context = args_contexts.get(&0).unwrap();
-            solution_mappings.mappings = solution_mappings.mappings.with_column(
-                col(first_context.as_str())
-                    .dt()
-                    .month()
-                    .alias(context.as_str()),
-            );
-            solution_mappings.rdf_node_types.insert(
-                context.as_str().to_string(),
-                RDFNodeType::Literal(xsd::UNSIGNED_INT.into_owned()),
-            );
-        }
-        Function::Day => {
-            assert_eq!(args.len(), 1);
-            let first_context = args_contexts.get(&0).unwrap();
-            solution_mappings.mappings = solution_mappings.mappings.with_column(
-                col(first_context.as_str())
-                    .dt()
-                    .day()
-                    .alias(context.as_str()),
-            );
-            solution_mappings.rdf_node_types.insert(
-                context.as_str().to_string(),
-                RDFNodeType::Literal(xsd::UNSIGNED_INT.into_owned()),
-            );
-        }
-        Function::Hours => {
-            assert_eq!(args.len(), 1);
-            let first_context = args_contexts.get(&0).unwrap();
-            solution_mappings.mappings = solution_mappings.mappings.with_column(
-                col(first_context.as_str())
-                    .dt()
-                    .hour()
-                    .alias(context.as_str()),
-            );
-            solution_mappings.rdf_node_types.insert(
-                context.as_str().to_string(),
-                RDFNodeType::Literal(xsd::UNSIGNED_INT.into_owned()),
-            );
+    //Wrong arity is an error even when the arguments are badly typed
+    check_function_arity(func, args.len(), &settings.functions)?;
+    let mut arg_types = vec![];
+    for i in 0..args.len() {
+        if let Some(c) = args_contexts.get(&i) {
+            arg_types.push(context_type(&solution_mappings, c)?.clone());
         }
-        Function::Minutes => {
-            assert_eq!(args.len(), 1);
-            let first_context = args_contexts.get(&0).unwrap();
-            solution_mappings.mappings = solution_mappings.mappings.with_column(
-                col(first_context.as_str())
-                    .dt()
-                    .minute()
-                    .alias(context.as_str()),
+    }
+    if splits_multitype_arguments(func)
+        && arg_types
+            .iter()
+            .any(|t| matches!(t, RDFNodeType::MultiType(..)))
+    {
+        return multitype_func_expression(
+            solution_mappings,
+            func,
+            args,
+            args_contexts,
+            &arg_types,
+            context,
+            settings,
+        );
+    }
+    if !argument_types_valid(func, &arg_types) {
+        //Type errors in arguments give an unbound result
+        solution_mappings.mappings = solution_mappings
+            .mappings
+            .with_column(Expr::Literal(LiteralValue::Null).alias(context.as_str()));
+        solution_mappings
+            .rdf_node_types
+            .insert(context.as_str().to_string(), RDFNodeType::None);
+        solution_mappings =
+            drop_inner_contexts(solution_mappings, &args_contexts.values().collect());
+        return Ok(solution_mappings);
+    }
+    match func {
+        Function::Year
+        | Function::Month
+        | Function::Day
+        | Function::Hours
+        | Function::Minutes
+        | Function::Seconds => {
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let value = col(first_context.as_str());
+            let is_gregorian = matches!(
+                &arg_types[0],
+                RDFNodeType::Literal(l) if matches!(l.as_ref(), xsd::G_YEAR | xsd::G_YEAR_MONTH)
             );
+            let expr = match func {
+                Function::Year if is_gregorian => gregorian_component(value, false),
+                Function::Month if is_gregorian => gregorian_component(value, true),
+                //Components are those of the local time of each value
+                Function::Year => datetime_component(value, |d| d.year() as i64),
+                Function::Month => datetime_component(value, |d| d.month() as i64),
+                Function::Day => datetime_component(value, |d| d.day() as i64),
+                Function::Hours => datetime_component(value, |d| d.hour() as i64),
+                Function::Minutes => datetime_component(value, |d| d.minute() as i64),
+                _ => {
+                    datetime_component(value, |d| {
+                        d.second() as i64 * 1_000_000_000 + d.nanosecond() as i64
+                    })
+                    .cast(DataType::Float64)
+                        / lit(1_000_000_000.0)
+                }
+            };
+            let t = if func == &Function::Seconds {
+                xsd::DECIMAL
+            } else {
+                xsd::INTEGER
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_column(expr.alias(context.as_str()));
             solution_mappings.rdf_node_types.insert(
                 context.as_str().to_string(),
-                RDFNodeType::Literal(xsd::UNSIGNED_INT.into_owned()),
+                RDFNodeType::Literal(t.into_owned()),
             );
         }
-        Function::Seconds => {
-            assert_eq!(args.len(), 1);
-            let first_context = args_contexts.get(&0).unwrap();
-            solution_mappings.mappings = solution_mappings.mappings.with_column(
-                col(first_context.as_str())
-                    .dt()
-                    .second()
-                    .alias(context.as_str()),
-            );
+        Function::Timezone | Function::Tz => {
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let (expr, t) = if func == &Function::Timezone {
+                (
+                    timezone(col(first_context.as_str())),
+                    xsd::DAY_TIME_DURATION,
+                )
+            } else {
+                (tz(col(first_context.as_str())), xsd::STRING)
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_column(expr.alias(context.as_str()));
             solution_mappings.rdf_node_types.insert(
                 context.as_str().to_string(),
-                RDFNodeType::Literal(xsd::UNSIGNED_INT.into_owned()),
+                RDFNodeType::Literal(t.into_owned()),
             );
         }
         Function::Abs => {
-            assert_eq!(args.len(), 1);
-            let first_context = args_contexts.get(&0).unwrap();
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
             solution_mappings.mappings = solution_mappings
                 .mappings
                 .with_column(col(first_context.as_str()).abs().alias(context.as_str()));
-            let existing_type = solution_mappings
-                .rdf_node_types
-                .get(first_context.as_str())
-                .unwrap();
+            let existing_type = context_type(&solution_mappings, first_context)?.clone();
             solution_mappings
                 .rdf_node_types
-                .insert(context.as_str().to_string(), existing_type.clone());
+                .insert(context.as_str().to_string(), existing_type);
         }
         Function::Ceil => {
-            assert_eq!(args.len(), 1);
-            let first_context = args_contexts.get(&0).unwrap();
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
             solution_mappings.mappings = solution_mappings
                 .mappings
                 .with_column(col(first_context.as_str()).ceil().alias(context.as_str()));
@@ -426,8 +757,8 @@ pub fn func_expression(
             );
         }
         Function::Floor => {
-            assert_eq!(args.len(), 1);
-            let first_context = args_contexts.get(&0).unwrap();
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
             solution_mappings.mappings = solution_mappings
                 .mappings
                 .with_column(col(first_context.as_str()).floor().alias(context.as_str()));
@@ -437,223 +768,641 @@ pub fn func_expression(
             );
         }
         Function::Concat => {
-            assert!(args.len() > 1);
             let SolutionMappings {
                 mappings,
                 rdf_node_types: datatypes,
             } = solution_mappings;
-            let cols: Vec<_> = (0..args.len())
-                .map(|i| col(args_contexts.get(&i).unwrap().as_str()))
-                .collect();
-            let new_mappings =
-                mappings.with_column(concat_str(cols, "", true).alias(context.as_str()));
+            let mut cols = vec![];
+            let mut tags = vec![];
+            for (i, t) in arg_types.iter().enumerate() {
+                let c = col(arg_context(func, &args_contexts, i)?.as_str());
+                cols.push(string_value(c.clone(), t));
+                tags.push(lang_tag(c, t));
+            }
+            //CONCAT() is the empty string
+            let concatenated = if cols.is_empty() {
+                lit("")
+            } else {
+                concat_str(cols, "", true)
+            };
+            let (expr, t) = if !arg_types.is_empty() && arg_types.iter().all(is_lang_string_type) {
+                //The language tag is kept only when all arguments share it
+                let first_tag = tags[0].clone();
+                let mut same_tag = Expr::Literal(LiteralValue::Boolean(true));
+                for tag in &tags[1..] {
+                    same_tag = same_tag.and(tag.clone().eq(first_tag.clone()));
+                }
+                lang_string_or_simple_literal(concatenated, first_tag, same_tag)
+            } else {
+                (concatenated, RDFNodeType::Literal(xsd::STRING.into_owned()))
+            };
+            let new_mappings = mappings.with_column(expr.alias(context.as_str()));
             solution_mappings = SolutionMappings::new(new_mappings, datatypes);
-            solution_mappings.rdf_node_types.insert(
-                context.as_str().to_string(),
-                RDFNodeType::Literal(xsd::STRING.into_owned()),
-            );
+            solution_mappings
+                .rdf_node_types
+                .insert(context.as_str().to_string(), t);
         }
         Function::Round => {
-            assert_eq!(args.len(), 1);
-            let first_context = args_contexts.get(&0).unwrap();
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let existing_type = context_type(&solution_mappings, first_context)?.clone();
+            //Integers are already rounded, decimals, floats and doubles round halves upwards
+            let integer =
+                matches!(&existing_type, RDFNodeType::Literal(l) if is_integer_type(l.as_ref()));
+            let expr = if integer {
+                col(first_context.as_str())
+            } else {
+                round_half_up(col(first_context.as_str()))
+            };
             solution_mappings.mappings = solution_mappings
                 .mappings
-                .with_column(col(first_context.as_str()).round(0).alias(context.as_str()));
-            let existing_type = solution_mappings
-                .rdf_node_types
-                .get(first_context.as_str())
-                .unwrap();
+                .with_column(expr.alias(context.as_str()));
             solution_mappings
                 .rdf_node_types
-                .insert(context.as_str().to_string(), existing_type.clone());
+                .insert(context.as_str().to_string(), existing_type);
         }
-        Function::Regex => {
-            if args.len() != 2 {
-                todo!("Unsupported amount of regex args {:?}", args);
+        Function::Regex | Function::Replace => {
+            let (min_args, max_args) = if func == &Function::Regex {
+                (2, 3)
             } else {
-                let first_context = args_contexts.get(&0).unwrap();
-                if let Expression::Literal(l) = args.get(1).unwrap() {
-                    solution_mappings.mappings = solution_mappings.mappings.with_column(
-                        col(first_context.as_str())
-                            .str()
-                            .contains(lit(l.value()), false)
-                            .alias(context.as_str()),
-                    );
-                    solution_mappings.rdf_node_types.insert(
-                        context.as_str().to_string(),
-                        RDFNodeType::Literal(xsd::STRING.into_owned()),
-                    );
+                (3, 4)
+            };
+            if args.len() < min_args || args.len() > max_args {
+                return Err(QueryProcessingError::WrongNumberOfArguments(
+                    func.to_string(),
+                    format!("{} or {}", min_args, max_args),
+                    args.len(),
+                ));
+            }
+            //Constant patterns are checked up front, per row patterns that are invalid give null
+            let flags_arg = if args.len() == max_args {
+                args.last()
+            } else {
+                None
+            };
+            if let Expression::Literal(pattern) = &args[1] {
+                let flags = match flags_arg {
+                    Some(Expression::Literal(flags)) => Some(flags.value()),
+                    Some(_) => None,
+                    None => Some(""),
+                };
+                if let Some(flags) = flags {
+                    regex_with_flags(pattern.value(), flags).map_err(|e| {
+                        QueryProcessingError::InvalidRegex(pattern.value().to_string(), e)
+                    })?;
+                }
+            }
+            if func == &Function::Replace {
+                if let Expression::Literal(replacement) = &args[2] {
+                    //Whether a replacement is valid does not depend on the number of groups
+                    if xpath_replacement(replacement.value(), 0).is_none() {
+                        return Err(QueryProcessingError::InvalidRegex(
+                            replacement.value().to_string(),
+                            "invalid replacement string".to_string(),
+                        ));
+                    }
                 }
             }
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let first_type = arg_types[0].clone();
+            let text = string_value(col(first_context.as_str()), &first_type);
+            let pattern = col(arg_context(func, &args_contexts, 1)?.as_str());
+            let flags = if flags_arg.is_some() {
+                col(arg_context(func, &args_contexts, max_args - 1)?.as_str())
+            } else {
+                lit("")
+            };
+            let (expr, t) = if func == &Function::Regex {
+                (
+                    regex_matches(text, pattern, flags),
+                    RDFNodeType::Literal(xsd::BOOLEAN.into_owned()),
+                )
+            } else {
+                let replacement = col(arg_context(func, &args_contexts, 2)?.as_str());
+                (
+                    with_string_value(
+                        regex_replace(text, pattern, replacement, flags),
+                        col(first_context.as_str()),
+                        &first_type,
+                    ),
+                    first_type,
+                )
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_column(expr.alias(context.as_str()));
+            solution_mappings
+                .rdf_node_types
+                .insert(context.as_str().to_string(), t);
         }
         Function::Custom(nn) => {
             let iri = nn.as_str();
-            if iri == xsd::INTEGER.as_str() {
-                assert_eq!(args.len(), 1);
-                let first_context = args_contexts.get(&0).unwrap();
-                solution_mappings.mappings = solution_mappings.mappings.with_column(
-                    col(first_context.as_str())
-                        .cast(DataType::Int64)
-                        .alias(context.as_str()),
-                );
+            if is_xsd_cast(nn.as_ref()) {
+                check_arity(func, args, 1)?;
+                let first_context = arg_context(func, &args_contexts, 0)?;
+                let parts: Vec<_> = typed_parts(col(first_context.as_str()), &arg_types[0])
+                    .into_iter()
+                    .map(|(t, e, present)| {
+                        when(present)
+                            .then(xsd_cast(e, &t, nn.as_ref()))
+                            .otherwise(Expr::Literal(LiteralValue::Null))
+                    })
+                    .collect();
+                solution_mappings.mappings = solution_mappings
+                    .mappings
+                    .with_column(coalesce(parts.as_slice()).alias(context.as_str()));
                 solution_mappings.rdf_node_types.insert(
                     context.as_str().to_string(),
-                    RDFNodeType::Literal(xsd::INTEGER.into_owned()),
-                );
-            } else if iri == xsd::STRING.as_str() {
-                assert_eq!(args.len(), 1);
-                let first_context = args_contexts.get(&0).unwrap();
-                solution_mappings.mappings = solution_mappings.mappings.with_column(
-                    col(first_context.as_str())
-                        .cast(DataType::String)
-                        .alias(context.as_str()),
-                );
-                solution_mappings.rdf_node_types.insert(
-                    context.as_str().to_string(),
-                    RDFNodeType::Literal(xsd::STRING.into_owned()),
-                );
-            } else if iri == DATETIME_AS_NANOS {
-                assert_eq!(args.len(), 1);
-                let first_context = args_contexts.get(&0).unwrap();
-                solution_mappings.mappings = solution_mappings.mappings.with_column(
-                    col(&first_context.as_str())
-                        .cast(DataType::Datetime(TimeUnit::Nanoseconds, None))
-                        .cast(DataType::UInt64)
-                        .alias(context.as_str()),
-                );
-                solution_mappings.rdf_node_types.insert(
-                    context.as_str().to_string(),
-                    RDFNodeType::Literal(xsd::INTEGER.into_owned()),
-                );
-            } else if iri == DATETIME_AS_SECONDS {
-                assert_eq!(args.len(), 1);
-                let first_context = args_contexts.get(&0).unwrap();
-                solution_mappings.mappings = solution_mappings.mappings.with_column(
-                    col(&first_context.as_str())
-                        .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
-                        .cast(DataType::UInt64)
-                        .div(lit(1000))
-                        .alias(context.as_str()),
-                );
-                solution_mappings.rdf_node_types.insert(
-                    context.as_str().to_string(),
-                    RDFNodeType::Literal(xsd::INTEGER.into_owned()),
-                );
-            } else if iri == NANOS_AS_DATETIME {
-                assert_eq!(args.len(), 1);
-                let first_context = args_contexts.get(&0).unwrap();
-                solution_mappings.mappings = solution_mappings.mappings.with_column(
-                    col(&first_context.as_str())
-                        .cast(DataType::Datetime(TimeUnit::Nanoseconds, None))
-                        .alias(context.as_str()),
-                );
-                solution_mappings.rdf_node_types.insert(
-                    context.as_str().to_string(),
-                    RDFNodeType::Literal(xsd::DATE_TIME.into_owned()),
-                );
-            } else if iri == SECONDS_AS_DATETIME {
-                assert_eq!(args.len(), 1);
-                let first_context = args_contexts.get(&0).unwrap();
-                solution_mappings.mappings = solution_mappings.mappings.with_column(
-                    col(&first_context.as_str())
-                        .mul(Expr::Literal(LiteralValue::UInt64(1000)))
-                        .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
-                        .alias(context.as_str()),
-                );
-                solution_mappings.rdf_node_types.insert(
-                    context.as_str().to_string(),
-                    RDFNodeType::Literal(xsd::DATE_TIME.into_owned()),
-                );
-            } else if iri == MODULUS {
-                assert_eq!(args.len(), 2);
-                let first_context = args_contexts.get(&0).unwrap();
-                let second_context = args_contexts.get(&1).unwrap();
-
-                solution_mappings.mappings = solution_mappings.mappings.with_column(
-                    (col(&first_context.as_str()) % col(&second_context.as_str()))
-                        .alias(context.as_str()),
-                );
-                solution_mappings.rdf_node_types.insert(
-                    context.as_str().to_string(),
-                    RDFNodeType::Literal(xsd::INTEGER.into_owned()),
-                );
-            } else if iri == FLOOR_DATETIME_TO_SECONDS_INTERVAL {
-                assert_eq!(args.len(), 2);
-                let first_context = args_contexts.get(&0).unwrap();
-                let second_context = args_contexts.get(&1).unwrap();
-
-                let first_as_seconds = col(&first_context.as_str())
-                    .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
-                    .cast(DataType::UInt64)
-                    .div(lit(1000));
-
-                solution_mappings.mappings = solution_mappings.mappings.with_column(
-                    ((first_as_seconds.clone()
-                        - (first_as_seconds % col(&second_context.as_str())))
-                    .mul(Expr::Literal(LiteralValue::UInt64(1000)))
-                    .cast(DataType::Datetime(TimeUnit::Milliseconds, None)))
-                    .alias(context.as_str()),
-                );
-                solution_mappings.rdf_node_types.insert(
-                    context.as_str().to_string(),
-                    RDFNodeType::Literal(xsd::DATE_TIME.into_owned()),
+                    RDFNodeType::Literal(nn.clone()),
                 );
+            } else if let Some(custom) = settings.functions.get(iri) {
+                if args.len() < custom.arity()
+                    || args.len() > custom.arity() + custom.optional_arguments()
+                {
+                    return Err(QueryProcessingError::WrongNumberOfArguments(
+                        func.to_string(),
+                        format!(
+                            "{} to {}",
+                            custom.arity(),
+                            custom.arity() + custom.optional_arguments()
+                        ),
+                        args.len(),
+                    ));
+                }
+                let (expr, t) = if let Some(t) = custom.output_type(&arg_types) {
+                    let mut exprs = vec![];
+                    for i in 0..args.len() {
+                        exprs.push(col(arg_context(func, &args_contexts, i)?.as_str()));
+                    }
+                    (custom.expression(exprs, &arg_types, args)?, t)
+                } else {
+                    (Expr::Literal(LiteralValue::Null), RDFNodeType::None)
+                };
+                solution_mappings.mappings = solution_mappings
+                    .mappings
+                    .with_column(expr.alias(context.as_str()));
+                solution_mappings
+                    .rdf_node_types
+                    .insert(context.as_str().to_string(), t);
             } else {
-                todo!("{:?}", nn)
+                return Err(QueryProcessingError::UnsupportedFunction(nn.to_string()));
             }
         }
-        Function::Contains => {
-            assert_eq!(args.len(), 2);
-            let first_context = args_contexts.get(&0).unwrap();
-            let second_context = args_contexts.get(&1).unwrap();
-
+        Function::Contains | Function::StrStarts | Function::StrEnds => {
+            check_arity(func, args, 2)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let second_context = arg_context(func, &args_contexts, 1)?;
+            let first_type = &arg_types[0];
+            let second_type = &arg_types[1];
+            let first = string_value(col(first_context.as_str()), first_type);
+            let second = string_value(col(second_context.as_str()), second_type);
+            let expr = match func {
+                Function::Contains => first.str().contains_literal(second),
+                Function::StrStarts => first.str().starts_with(second),
+                _ => first.str().ends_with(second),
+            };
+            let expr = if !string_arguments_compatible(first_type, second_type) {
+                Expr::Literal(LiteralValue::Null).cast(DataType::Boolean)
+            } else if is_lang_string_type(second_type) {
+                when(
+                    lang_tag(col(first_context.as_str()), first_type)
+                        .eq(lang_tag(col(second_context.as_str()), second_type)),
+                )
+                .then(expr)
+                .otherwise(Expr::Literal(LiteralValue::Null))
+            } else {
+                expr
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_column(expr.alias(context.as_str()));
+            solution_mappings.rdf_node_types.insert(
+                context.as_str().to_string(),
+                RDFNodeType::Literal(xsd::BOOLEAN.into_owned()),
+            );
+        }
+        Function::Lang => {
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
             solution_mappings.mappings = solution_mappings.mappings.with_column(
-                (col(&first_context.as_str())
-                    .str()
-                    .contains_literal(col(&second_context.as_str())))
-                .alias(context.as_str()),
+                lang_tag(col(first_context.as_str()), &arg_types[0]).alias(context.as_str()),
+            );
+            solution_mappings.rdf_node_types.insert(
+                context.as_str().to_string(),
+                RDFNodeType::Literal(xsd::STRING.into_owned()),
+            );
+        }
+        Function::LangMatches => {
+            check_arity(func, args, 2)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let second_context = arg_context(func, &args_contexts, 1)?;
+            let tag = col(first_context.as_str()).str().to_lowercase();
+            let range = col(second_context.as_str()).str().to_lowercase();
+            solution_mappings.mappings = solution_mappings.mappings.with_column(
+                when(range.clone().eq(lit("*")))
+                    .then(tag.clone().neq(lit("")))
+                    .otherwise(
+                        tag.clone()
+                            .eq(range.clone())
+                            .or(tag
+                                .str()
+                                .starts_with(concat_str([range, lit("-")], "", false))),
+                    )
+                    .alias(context.as_str()),
             );
             solution_mappings.rdf_node_types.insert(
                 context.as_str().to_string(),
                 RDFNodeType::Literal(xsd::BOOLEAN.into_owned()),
             );
         }
-        Function::StrStarts => {
-            assert_eq!(args.len(), 2);
-            let first_context = args_contexts.get(&0).unwrap();
-            let second_context = args_contexts.get(&1).unwrap();
-
+        Function::StrLang => {
+            check_arity(func, args, 2)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let second_context = arg_context(func, &args_contexts, 1)?;
             solution_mappings.mappings = solution_mappings.mappings.with_column(
-                (col(&first_context.as_str())
-                    .str()
-                    .starts_with(col(&second_context.as_str())))
+                as_struct(vec![
+                    col(first_context.as_str()).alias(LANG_STRING_VALUE_FIELD),
+                    col(second_context.as_str()).alias(LANG_STRING_LANG_FIELD),
+                ])
                 .alias(context.as_str()),
             );
             solution_mappings.rdf_node_types.insert(
                 context.as_str().to_string(),
-                RDFNodeType::Literal(xsd::BOOLEAN.into_owned()),
+                RDFNodeType::Literal(rdf::LANG_STRING.into_owned()),
             );
         }
-        Function::StrEnds => {
-            assert_eq!(args.len(), 2);
-            let first_context = args_contexts.get(&0).unwrap();
-            let second_context = args_contexts.get(&1).unwrap();
-
+        Function::StrLen | Function::EncodeForUri => {
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let value = string_value(col(first_context.as_str()), &arg_types[0]);
+            let (expr, t) = if func == &Function::StrLen {
+                (str_len(value), xsd::INTEGER)
+            } else {
+                (encode_for_uri(value), xsd::STRING)
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_column(expr.alias(context.as_str()));
+            solution_mappings.rdf_node_types.insert(
+                context.as_str().to_string(),
+                RDFNodeType::Literal(t.into_owned()),
+            );
+        }
+        Function::UCase | Function::LCase | Function::SubStr => {
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let first_type = arg_types[0].clone();
+            let value = string_value(col(first_context.as_str()), &first_type);
+            let expr = match func {
+                Function::UCase => {
+                    check_arity(func, args, 1)?;
+                    value.str().to_uppercase()
+                }
+                Function::LCase => {
+                    check_arity(func, args, 1)?;
+                    value.str().to_lowercase()
+                }
+                _ => {
+                    if args.len() != 2 && args.len() != 3 {
+                        return Err(QueryProcessingError::WrongNumberOfArguments(
+                            func.to_string(),
+                            "2 or 3".to_string(),
+                            args.len(),
+                        ));
+                    }
+                    let length = if args.len() == 3 {
+                        Some(col(arg_context(func, &args_contexts, 2)?.as_str()))
+                    } else {
+                        None
+                    };
+                    substr(
+                        value,
+                        col(arg_context(func, &args_contexts, 1)?.as_str()),
+                        length,
+                    )
+                }
+            };
             solution_mappings.mappings = solution_mappings.mappings.with_column(
-                (col(&first_context.as_str())
-                    .str()
-                    .ends_with(col(&second_context.as_str())))
+                with_string_value(expr, col(first_context.as_str()), &first_type)
+                    .alias(context.as_str()),
+            );
+            solution_mappings
+                .rdf_node_types
+                .insert(context.as_str().to_string(), first_type);
+        }
+        Function::StrBefore | Function::StrAfter => {
+            check_arity(func, args, 2)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let second_context = arg_context(func, &args_contexts, 1)?;
+            let first_type = &arg_types[0];
+            let second_type = &arg_types[1];
+            let first = string_value(col(first_context.as_str()), first_type);
+            let second = string_value(col(second_context.as_str()), second_type);
+            let value = if func == &Function::StrBefore {
+                str_before(first.clone(), second.clone())
+            } else {
+                str_after(first.clone(), second.clone())
+            };
+            let (expr, t) = if !string_arguments_compatible(first_type, second_type) {
+                (Expr::Literal(LiteralValue::Null), RDFNodeType::None)
+            } else if is_lang_string_type(first_type) {
+                //The language tag is kept only when the second argument is found
+                let found = first.str().contains_literal(second);
+                let (expr, t) = lang_string_or_simple_literal(
+                    value,
+                    lang_tag(col(first_context.as_str()), first_type),
+                    found,
+                );
+                let expr = if is_lang_string_type(second_type) {
+                    when(
+                        lang_tag(col(first_context.as_str()), first_type)
+                            .eq(lang_tag(col(second_context.as_str()), second_type)),
+                    )
+                    .then(expr)
+                    .otherwise(Expr::Literal(LiteralValue::Null))
+                } else {
+                    expr
+                };
+                (expr, t)
+            } else {
+                (value, RDFNodeType::Literal(xsd::STRING.into_owned()))
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_column(expr.alias(context.as_str()));
+            solution_mappings
+                .rdf_node_types
+                .insert(context.as_str().to_string(), t);
+        }
+        Function::Iri => {
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let expr = if arg_types[0] == RDFNodeType::IRI {
+                col(first_context.as_str())
+            } else {
+                resolve_iri(
+                    col(first_context.as_str()),
+                    settings.base_iri.as_ref().map(|b| b.as_str().to_string()),
+                )
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_column(expr.alias(context.as_str()));
+            solution_mappings
+                .rdf_node_types
+                .insert(context.as_str().to_string(), RDFNodeType::IRI);
+        }
+        Function::BNode => {
+            if args.len() > 1 {
+                return Err(QueryProcessingError::WrongNumberOfArguments(
+                    func.to_string(),
+                    "0 or 1".to_string(),
+                    args.len(),
+                ));
+            }
+            //Labels are unique to this call and to the row, and to the argument when there is one
+            let call_site = call_site_uuid(settings.seed, context.as_str());
+            let prefix = format!("bnode_{}_", call_site.simple());
+            let mut parts = vec![lit(prefix), col(context.as_str()).cast(DataType::String)];
+            if !args.is_empty() {
+                let first_context = arg_context(func, &args_contexts, 0)?;
+                parts.push(lit("_"));
+                parts.push(hex_encode(col(first_context.as_str())));
+            }
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_row_index(context.as_str(), None)
+                .with_column(concat_str(parts, "", false).alias(context.as_str()));
+            solution_mappings
+                .rdf_node_types
+                .insert(context.as_str().to_string(), RDFNodeType::BlankNode);
+        }
+        Function::StrDt => {
+            check_arity(func, args, 2)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            //A column has a single datatype, so the datatype must be a constant, and is
+            //unbound otherwise
+            let (expr, t) = match &args[1] {
+                Expression::NamedNode(datatype) if datatype.as_ref() != rdf::LANG_STRING => (
+                    parse_lexical_form(col(first_context.as_str()), datatype.as_ref()),
+                    RDFNodeType::Literal(datatype.clone()),
+                ),
+                _ => (Expr::Literal(LiteralValue::Null), RDFNodeType::None),
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_column(expr.alias(context.as_str()));
+            solution_mappings
+                .rdf_node_types
+                .insert(context.as_str().to_string(), t);
+        }
+        Function::Uuid | Function::StrUuid | Function::Rand => {
+            check_arity(func, args, 0)?;
+            //The row index gives the generated column its length and seeds each row
+            let index = col(context.as_str());
+            let call_site = context.as_str().to_string();
+            let (expr, t) = match func {
+                Function::Uuid => (
+                    random_uuids(index, settings.seed, call_site, "urn:uuid:"),
+                    RDFNodeType::IRI,
+                ),
+                Function::StrUuid => (
+                    random_uuids(index, settings.seed, call_site, ""),
+                    RDFNodeType::Literal(xsd::STRING.into_owned()),
+                ),
+                _ => (
+                    random_doubles(index, settings.seed, call_site),
+                    RDFNodeType::Literal(xsd::DOUBLE.into_owned()),
+                ),
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_row_index(context.as_str(), None)
+                .with_column(expr.alias(context.as_str()));
+            solution_mappings
+                .rdf_node_types
+                .insert(context.as_str().to_string(), t);
+        }
+        Function::Now => {
+            check_arity(func, args, 0)?;
+            //Settings from QuerySettings::for_query give the same time for every call
+            solution_mappings.mappings = solution_mappings.mappings.with_column(
+                Expr::Literal(LiteralValue::DateTime(
+                    settings.now.unwrap_or_else(Utc::now).timestamp_micros(),
+                    TimeUnit::Microseconds,
+                    Some("UTC".to_string()),
+                ))
                 .alias(context.as_str()),
             );
+            solution_mappings.rdf_node_types.insert(
+                context.as_str().to_string(),
+                RDFNodeType::Literal(xsd::DATE_TIME.into_owned()),
+            );
+        }
+        Function::Md5 | Function::Sha1 | Function::Sha256 | Function::Sha384 | Function::Sha512 => {
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let value = col(first_context.as_str());
+            let expr = match func {
+                Function::Md5 => md5(value),
+                Function::Sha1 => sha1(value),
+                Function::Sha256 => sha256(value),
+                Function::Sha384 => sha384(value),
+                _ => sha512(value),
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_column(expr.alias(context.as_str()));
+            solution_mappings.rdf_node_types.insert(
+                context.as_str().to_string(),
+                RDFNodeType::Literal(xsd::STRING.into_owned()),
+            );
+        }
+        Function::IsIri | Function::IsBlank | Function::IsLiteral | Function::IsNumeric => {
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let test: fn(&RDFNodeType) -> bool = match func {
+                Function::IsIri => |t: &RDFNodeType| t == &RDFNodeType::IRI,
+                Function::IsBlank => |t: &RDFNodeType| t == &RDFNodeType::BlankNode,
+                Function::IsLiteral => |t: &RDFNodeType| matches!(t, RDFNodeType::Literal(..)),
+                _ => is_numeric_type,
+            };
+            solution_mappings.mappings = solution_mappings.mappings.with_column(
+                term_test(col(first_context.as_str()), &arg_types[0], test).alias(context.as_str()),
+            );
             solution_mappings.rdf_node_types.insert(
                 context.as_str().to_string(),
                 RDFNodeType::Literal(xsd::BOOLEAN.into_owned()),
             );
         }
+        Function::Datatype => {
+            check_arity(func, args, 1)?;
+            let first_context = arg_context(func, &args_contexts, 0)?;
+            let parts: Vec<_> = typed_parts(col(first_context.as_str()), &arg_types[0])
+                .into_iter()
+                .filter_map(|(t, _, present)| {
+                    if let RDFNodeType::Literal(nn) = t {
+                        Some(
+                            when(present)
+                                .then(Expr::Literal(sparql_named_node_to_polars_literal_value(
+                                    &nn,
+                                )))
+                                .otherwise(Expr::Literal(LiteralValue::Null)),
+                        )
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            let (expr, t) = if parts.is_empty() {
+                (Expr::Literal(LiteralValue::Null), RDFNodeType::None)
+            } else {
+                (coalesce(parts.as_slice()), RDFNodeType::IRI)
+            };
+            solution_mappings.mappings = solution_mappings
+                .mappings
+                .with_column(expr.alias(context.as_str()));
+            solution_mappings
+                .rdf_node_types
+                .insert(context.as_str().to_string(), t);
+        }
         _ => {
-            todo!("{}", func)
+            return Err(QueryProcessingError::UnsupportedFunction(func.to_string()));
+        }
+    }
+    solution_mappings = drop_inner_contexts(solution_mappings, &args_contexts.values().collect());
+    Ok(solution_mappings)
+}
+
+//Evaluates a function for each combination of the base types of its arguments, at most one of
+//which is present in a row, and selects the results as the branches of a COALESCE
+fn multitype_func_expression(
+    mut solution_mappings: SolutionMappings,
+    func: &Function,
+    args: &Vec<Expression>,
+    args_contexts: HashMap<usize, Context>,
+    arg_types: &[RDFNodeType],
+    context: &Context,
+    settings: &QuerySettings,
+) -> Result<SolutionMappings, QueryProcessingError> {
+    let mut options = vec![];
+    for (i, t) in arg_types.iter().enumerate() {
+        let parts = typed_parts(col(arg_context(func, &args_contexts, i)?.as_str()), t);
+        let multi = matches!(t, RDFNodeType::MultiType(..));
+        options.push(
+            parts
+                .into_iter()
+                .map(|(t, e, present)| (t, e, present, multi))
+                .collect(),
+        );
+    }
+    let mut choices = vec![];
+    for (k, combination) in combinations(&options).into_iter().enumerate() {
+        let combination_context = context.extension_with(PathEntry::Coalesce(k as u16));
+        let mut combination_contexts = HashMap::new();
+        let mut present = Expr::Literal(LiteralValue::Boolean(true));
+        for (i, (t, e, part_present, multi)) in combination.into_iter().enumerate() {
+            let c = combination_context.extension_with(PathEntry::FunctionCall(i as u16));
+            solution_mappings.mappings =
+                solution_mappings.mappings.with_column(e.alias(c.as_str()));
+            solution_mappings
+                .rdf_node_types
+                .insert(c.as_str().to_string(), t);
+            if multi {
+                present = present.and(part_present);
+            }
+            combination_contexts.insert(i, c);
         }
+        solution_mappings = func_expression(
+            solution_mappings,
+            func,
+            args,
+            combination_contexts,
+            &combination_context,
+            settings,
+        )?;
+        let t = context_type(&solution_mappings, &combination_context)?.clone();
+        choices.push((combination_context, present, t));
     }
+    let result_types: Vec<_> = choices.iter().map(|(_, _, t)| t.clone()).collect();
+    let target_type = unified_type(&result_types);
+    let expr = if let RDFNodeType::MultiType(target_types) = &target_type {
+        let (mappings, branch_types) = branches_to_multitype(
+            solution_mappings.mappings,
+            choices.iter().map(|(c, _, t)| (c, t)).collect(),
+        );
+        solution_mappings.mappings = mappings;
+        let branches = choices
+            .iter()
+            .zip(branch_types)
+            .map(|((c, present, _), types)| (present.clone(), col(c.as_str()), types))
+            .collect();
+        select_multitype(branches, target_types)
+    } else {
+        let parts: Vec<_> = choices
+            .iter()
+            .filter(|(_, _, t)| t != &RDFNodeType::None)
+            .map(|(c, present, _)| {
+                when(present.clone())
+                    .then(col(c.as_str()))
+                    .otherwise(Expr::Literal(LiteralValue::Null))
+            })
+            .collect();
+        if parts.is_empty() {
+            Expr::Literal(LiteralValue::Null)
+        } else {
+            coalesce(parts.as_slice())
+        }
+    };
+    solution_mappings.mappings = solution_mappings
+        .mappings
+        .with_column(expr.alias(context.as_str()));
+    solution_mappings
+        .rdf_node_types
+        .insert(context.as_str().to_string(), target_type);
+    solution_mappings = drop_inner_contexts(
+        solution_mappings,
+        &choices.iter().map(|(c, _, _)| c).collect(),
+    );
     solution_mappings = drop_inner_contexts(solution_mappings, &args_contexts.values().collect());
     Ok(solution_mappings)
 }
@@ -691,6 +1440,237 @@ pub fn in_expression(
     Ok(solution_mappings)
 }
 
+pub fn same_term(
+    mut solution_mappings: SolutionMappings,
+    left_context: &Context,
+    right_context: &Context,
+    context: &Context,
+) -> Result<SolutionMappings, QueryProcessingError> {
+    let left_type = context_type(&solution_mappings, left_context)?.clone();
+    let right_type = context_type(&solution_mappings, right_context)?.clone();
+    //Terms are the same only when they have the same type and value
+    let mut exprs = vec![];
+    for (lt, le, lp) in typed_parts(col(left_context.as_str()), &left_type) {
+        for (rt, re, rp) in typed_parts(col(right_context.as_str()), &right_type) {
+            let same = if lt != rt {
+                Expr::Literal(LiteralValue::Boolean(false))
+            } else if is_lang_string_type(&lt) {
+                string_value(le.clone(), &lt)
+                    .eq(string_value(re.clone(), &rt))
+                    .and(lang_tag(le.clone(), &lt).eq(lang_tag(re, &rt)))
+            } else {
+                le.clone().eq(re)
+            };
+            exprs.push(
+                when(lp.clone().and(rp))
+                    .then(same)
+                    .otherwise(Expr::Literal(LiteralValue::Null)),
+            );
+        }
+    }
+    solution_mappings.mappings = solution_mappings
+        .mappings
+        .with_column(coalesce(exprs.as_slice()).alias(context.as_str()));
+    solution_mappings.rdf_node_types.insert(
+        context.as_str().to_string(),
+        RDFNodeType::Literal(xsd::BOOLEAN.into_owned()),
+    );
+    solution_mappings = drop_inner_contexts(solution_mappings, &vec![left_context, right_context]);
+    Ok(solution_mappings)
+}
+
+pub fn effective_boolean_value(expr: Expr, t: &RDFNodeType) -> Expr {
+    match t {
+        RDFNodeType::MultiType(types) => {
+            let mut ebv_exprs = vec![];
+            for bt in types {
+                if base_type_has_ebv(bt) {
+                    let field_type = if bt.is_lang_string() {
+                        RDFNodeType::Literal(xsd::STRING.into_owned())
+                    } else {
+                        bt.as_rdf_node_type()
+                    };
+                    ebv_exprs.push(effective_boolean_value(
+                        multitype_field(expr.clone(), bt),
+                        &field_type,
+                    ));
+                }
+            }
+            if ebv_exprs.is_empty() {
+                Expr::Literal(LiteralValue::Null).cast(DataType::Boolean)
+            } else {
+                coalesce(ebv_exprs.as_slice())
+            }
+        }
+        RDFNodeType::Literal(l) => {
+            if literal_is_boolean(l.as_ref()) {
+                expr
+            } else if literal_is_string(l.as_ref()) {
+                expr.neq(lit(""))
+            } else if l.as_ref() == rdf::LANG_STRING {
+                expr.struct_()
+                    .field_by_name(LANG_STRING_VALUE_FIELD)
+                    .neq(lit(""))
+            } else if is_float_type(t) || l.as_ref() == xsd::DECIMAL {
+                expr.clone().neq(lit(0)).and(expr.is_nan().not())
+            } else if literal_is_numeric(l.as_ref()) {
+                expr.neq(lit(0))
+            } else {
+                Expr::Literal(LiteralValue::Null).cast(DataType::Boolean)
+            }
+        }
+        _ => Expr::Literal(LiteralValue::Null).cast(DataType::Boolean),
+    }
+}
+
+//Tests the kind of term in each row, at most one part of a multi typed expression is present
+fn term_test(expr: Expr, t: &RDFNodeType, test: fn(&RDFNodeType) -> bool) -> Expr {
+    let parts: Vec<_> = typed_parts(expr, t)
+        .into_iter()
+        .map(|(t, _, present)| {
+            when(present)
+                .then(Expr::Literal(LiteralValue::Boolean(test(&t))))
+                .otherwise(Expr::Literal(LiteralValue::Null))
+        })
+        .collect();
+    coalesce(parts.as_slice())
+}
+
+fn base_type_has_ebv(bt: &BaseRDFNodeType) -> bool {
+    if bt.is_lang_string() {
+        true
+    } else if let BaseRDFNodeType::Literal(l) = bt {
+        literal_is_boolean(l.as_ref())
+            || literal_is_string(l.as_ref())
+            || literal_is_numeric(l.as_ref())
+    } else {
+        false
+    }
+}
+
+pub fn multitype_field(expr: Expr, bt: &BaseRDFNodeType) -> Expr {
+    if bt.is_lang_string() {
+        expr.struct_().field_by_name(LANG_STRING_VALUE_FIELD)
+    } else {
+        expr.struct_().field_by_name(&base_col_name(bt))
+    }
+}
+
+pub fn string_value(expr: Expr, t: &RDFNodeType) -> Expr {
+    if is_lang_string_type(t) {
+        expr.struct_().field_by_name(LANG_STRING_VALUE_FIELD)
+    } else {
+        expr
+    }
+}
+
+//Replaces the value of a string while keeping any language tag
+fn with_string_value(value: Expr, original: Expr, t: &RDFNodeType) -> Expr {
+    if is_lang_string_type(t) {
+        as_struct(vec![
+            value.alias(LANG_STRING_VALUE_FIELD),
+            original
+                .struct_()
+                .field_by_name(LANG_STRING_LANG_FIELD)
+                .alias(LANG_STRING_LANG_FIELD),
+        ])
+    } else {
+        value
+    }
+}
+
+//Gives a language tagged string where keep_lang holds and a simple literal elsewhere
+fn lang_string_or_simple_literal(value: Expr, lang: Expr, keep_lang: Expr) -> (Expr, RDFNodeType) {
+    let string_type = BaseRDFNodeType::Literal(xsd::STRING.into_owned());
+    let expr = as_struct(vec![
+        when(keep_lang.clone())
+            .then(value.clone())
+            .otherwise(Expr::Literal(LiteralValue::Null))
+            .alias(LANG_STRING_VALUE_FIELD),
+        when(keep_lang.clone())
+            .then(lang)
+            .otherwise(Expr::Literal(LiteralValue::Null))
+            .alias(LANG_STRING_LANG_FIELD),
+        when(keep_lang.not())
+            .then(value)
+            .otherwise(Expr::Literal(LiteralValue::Null))
+            .alias(&base_col_name(&string_type)),
+    ]);
+    let mut types = vec![
+        BaseRDFNodeType::Literal(rdf::LANG_STRING.into_owned()),
+        string_type,
+    ];
+    types.sort();
+    (expr, RDFNodeType::MultiType(types))
+}
+
+pub fn lang_tag(expr: Expr, t: &RDFNodeType) -> Expr {
+    match t {
+        RDFNodeType::Literal(l) if l.as_ref() == rdf::LANG_STRING => {
+            expr.struct_().field_by_name(LANG_STRING_LANG_FIELD)
+        }
+        RDFNodeType::Literal(..) => when(expr.is_not_null())
+            .then(lit(""))
+            .otherwise(Expr::Literal(LiteralValue::Null)),
+        RDFNodeType::MultiType(types) => {
+            let mut tags = vec![];
+            for bt in types {
+                if bt.is_lang_string() {
+                    tags.push(expr.clone().struct_().field_by_name(LANG_STRING_LANG_FIELD));
+                } else if let BaseRDFNodeType::Literal(..) = bt {
+                    tags.push(
+                        when(multitype_field(expr.clone(), bt).is_not_null())
+                            .then(lit(""))
+                            .otherwise(Expr::Literal(LiteralValue::Null)),
+                    );
+                }
+            }
+            if tags.is_empty() {
+                Expr::Literal(LiteralValue::Null).cast(DataType::String)
+            } else {
+                coalesce(tags.as_slice())
+            }
+        }
+        _ => Expr::Literal(LiteralValue::Null).cast(DataType::String),
+    }
+}
+
+fn check_arity(
+    func: &Function,
+    args: &[Expression],
+    expected: usize,
+) -> Result<(), QueryProcessingError> {
+    if args.len() != expected {
+        Err(QueryProcessingError::WrongNumberOfArguments(
+            func.to_string(),
+            expected.to_string(),
+            args.len(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn arg_context<'a>(
+    func: &Function,
+    args_contexts: &'a HashMap<usize, Context>,
+    i: usize,
+) -> Result<&'a Context, QueryProcessingError> {
+    args_contexts
+        .get(&i)
+        .ok_or_else(|| QueryProcessingError::MissingArgumentContext(func.to_string(), i))
+}
+
+fn context_type<'a>(
+    solution_mappings: &'a SolutionMappings,
+    context: &Context,
+) -> Result<&'a RDFNodeType, QueryProcessingError> {
+    solution_mappings
+        .rdf_node_types
+        .get(context.as_str())
+        .ok_or_else(|| QueryProcessingError::DatatypeNotFound(context.as_str().to_string()))
+}
+
 pub fn drop_inner_contexts(mut sm: SolutionMappings, contexts: &Vec<&Context>) -> SolutionMappings {
     let mut inner = vec![];
     for c in contexts {
@@ -702,27 +1682,1081 @@ pub fn drop_inner_contexts(mut sm: SolutionMappings, contexts: &Vec<&Context>) -
     sm
 }
 
-pub fn compatible_operation(expression: Expression, l1: NamedNodeRef, l2: NamedNodeRef) -> bool {
-    let compat = match expression {
-        Expression::Equal(..)
-        | Expression::LessOrEqual(..)
-        | Expression::GreaterOrEqual(..)
-        | Expression::Greater(..)
-        | Expression::Less(..) => {
-            (literal_is_numeric(l1) && literal_is_numeric(l2))
-                || (literal_is_boolean(l1) && literal_is_boolean(l2))
-                || (literal_is_string(l1) && literal_is_string(l2))
-                || (literal_is_datetime(l1) && literal_is_datetime(l2))
-        }
-        Expression::Or(..) | Expression::And(..) => {
-            literal_is_boolean(l1) && literal_is_boolean(l2)
-        }
-        Expression::Add(..)
-        | Expression::Subtract(..)
-        | Expression::Multiply(..)
-        | Expression::Divide(..) => literal_is_numeric(l1) && literal_is_numeric(l2),
-        _ => todo!(),
+pub fn compatible_operation(
+    expression: Expression,
+    l1: NamedNodeRef,
+    l2: NamedNodeRef,
+) -> Result<bool, QueryProcessingError> {
+    let op = match expression {
+        Expression::Equal(..) => Operator::Eq,
+        Expression::LessOrEqual(..) => Operator::LtEq,
+        Expression::GreaterOrEqual(..) => Operator::GtEq,
+        Expression::Greater(..) => Operator::Gt,
+        Expression::Less(..) => Operator::Lt,
+        Expression::Or(..) => Operator::Or,
+        Expression::And(..) => Operator::And,
+        Expression::Add(..) => Operator::Plus,
+        Expression::Subtract(..) => Operator::Minus,
+        Expression::Multiply(..) => Operator::Multiply,
+        Expression::Divide(..) => Operator::Divide,
+        _ => {
+            return Err(QueryProcessingError::UnsupportedExpression(
+                expression.to_string(),
+            ))
+        }
     };
-    println!("Compat: {}, {:?}, {:?}", compat, l1, l2);
-    compat
+    let t1 = RDFNodeType::Literal(l1.into_owned());
+    let t2 = RDFNodeType::Literal(l2.into_owned());
+    let compat = match op {
+        Operator::Or | Operator::And => literal_is_boolean(l1) && literal_is_boolean(l2),
+        Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide => {
+            binary_operator_type(op, &t1, &t2).is_some()
+        }
+        _ => comparable_types(op, &t1, &t2),
+    };
+    debug!("Compat: {}, {:?}, {:?}", compat, l1, l2);
+    Ok(compat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::{DataFrame, NamedFrom};
+
+    fn solution_mappings(
+        columns: Vec<Series>,
+        types: Vec<(&str, RDFNodeType)>,
+    ) -> SolutionMappings {
+        let types = types.into_iter().map(|(c, t)| (c.to_string(), t)).collect();
+        SolutionMappings::new(DataFrame::new(columns).unwrap().lazy(), types)
+    }
+
+    fn rows(n: i64) -> SolutionMappings {
+        solution_mappings(vec![Series::new("row", (0..n).collect::<Vec<_>>())], vec![])
+    }
+
+    fn context(i: u16) -> Context {
+        Context::new().extension_with(PathEntry::FunctionCall(i))
+    }
+
+    fn with_literal(
+        solution_mappings: SolutionMappings,
+        l: Literal,
+        c: &Context,
+    ) -> SolutionMappings {
+        literal(solution_mappings, &l, c).unwrap()
+    }
+
+    fn integer(i: i64) -> Literal {
+        Literal::new_typed_literal(i.to_string(), xsd::INTEGER)
+    }
+
+    fn column(solution_mappings: SolutionMappings, c: &Context) -> Series {
+        let df = solution_mappings.mappings.collect().unwrap();
+        df.column(c.as_str()).unwrap().clone()
+    }
+
+    fn filtered_height(
+        solution_mappings: SolutionMappings,
+        expression: Expression,
+        c: &Context,
+    ) -> usize {
+        let settings = QuerySettings::default();
+        let sm =
+            crate::graph_patterns::filter(solution_mappings, &expression, c, &settings).unwrap();
+        sm.mappings.collect().unwrap().height()
+    }
+
+    fn cast_to_integer(solution_mappings: SolutionMappings, arg: Expression) -> SolutionMappings {
+        func_expression(
+            solution_mappings,
+            &Function::Custom(xsd::INTEGER.into_owned()),
+            &vec![arg],
+            HashMap::from([(0, context(0))]),
+            &context(1),
+            &QuerySettings::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn unknown_custom_function_is_unsupported() {
+        let func = Function::Custom(NamedNode::new_unchecked("http://example.org/unknown"));
+        let result = func_expression(
+            rows(1),
+            &func,
+            &vec![],
+            HashMap::new(),
+            &context(0),
+            &QuerySettings::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::UnsupportedFunction(..))
+        ));
+    }
+
+    #[test]
+    fn wrong_number_of_arguments() {
+        let sm = with_literal(rows(1), Literal::new_simple_literal("a"), &context(0));
+        let sm = with_literal(sm, Literal::new_simple_literal("b"), &context(1));
+        let args = vec![
+            Expression::Literal(Literal::new_simple_literal("a")),
+            Expression::Literal(Literal::new_simple_literal("b")),
+        ];
+        let args_contexts = HashMap::from([(0, context(0)), (1, context(1))]);
+        let result = func_expression(
+            sm,
+            &Function::StrLen,
+            &args,
+            args_contexts,
+            &context(2),
+            &QuerySettings::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::WrongNumberOfArguments(..))
+        ));
+    }
+
+    #[test]
+    fn wrong_number_of_badly_typed_arguments() {
+        let sm = with_literal(rows(1), integer(1), &context(0));
+        let sm = with_literal(sm, integer(2), &context(1));
+        let args = vec![
+            Expression::Literal(integer(1)),
+            Expression::Literal(integer(2)),
+        ];
+        let args_contexts = HashMap::from([(0, context(0)), (1, context(1))]);
+        let result = func_expression(
+            sm,
+            &Function::StrLen,
+            &args,
+            args_contexts,
+            &context(2),
+            &QuerySettings::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::WrongNumberOfArguments(..))
+        ));
+    }
+
+    #[test]
+    fn missing_argument_context() {
+        let args = vec![Expression::Literal(Literal::new_simple_literal("a"))];
+        let result = func_expression(
+            rows(1),
+            &Function::StrLen,
+            &args,
+            HashMap::new(),
+            &context(1),
+            &QuerySettings::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::MissingArgumentContext(..))
+        ));
+    }
+
+    #[test]
+    fn unsupported_binary_operator() {
+        let sm = with_literal(rows(1), integer(1), &context(0));
+        let sm = with_literal(sm, integer(2), &context(1));
+        let result = binary_expression(sm, Operator::Xor, &context(0), &context(1), &context(2));
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::UnsupportedBinaryOperator(..))
+        ));
+    }
+
+    #[test]
+    fn unsupported_expression() {
+        let not = Expression::Not(Box::new(Expression::Literal(Literal::from(true))));
+        let result = compatible_operation(not, xsd::BOOLEAN, xsd::BOOLEAN);
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::UnsupportedExpression(..))
+        ));
+    }
+
+    #[test]
+    fn datatype_not_found() {
+        let sm = with_literal(rows(1), integer(1), &context(0));
+        let result = binary_expression(sm, Operator::Plus, &context(0), &context(1), &context(2));
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::DatatypeNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn adding_a_string_and_a_number_is_unbound() {
+        let sm = with_literal(rows(1), Literal::new_simple_literal("abc"), &context(0));
+        let sm = with_literal(sm, integer(1), &context(1));
+        let sm =
+            binary_expression(sm, Operator::Plus, &context(0), &context(1), &context(2)).unwrap();
+        assert_eq!(
+            sm.rdf_node_types.get(context(2).as_str()),
+            Some(&RDFNodeType::None)
+        );
+        assert_eq!(column(sm, &context(2)).null_count(), 1);
+    }
+
+    #[test]
+    fn casting_an_invalid_lexical_form_is_unbound() {
+        let arg = Literal::new_simple_literal("x");
+        let sm = with_literal(rows(1), arg.clone(), &context(0));
+        let sm = cast_to_integer(sm, Expression::Literal(arg));
+        assert_eq!(column(sm, &context(1)).null_count(), 1);
+    }
+
+    #[test]
+    fn integer_division_by_zero_is_unbound() {
+        let sm = with_literal(rows(1), integer(1), &context(0));
+        let sm = with_literal(sm, integer(0), &context(1));
+        let sm =
+            binary_expression(sm, Operator::Divide, &context(0), &context(1), &context(2)).unwrap();
+        assert_eq!(column(sm, &context(2)).null_count(), 1);
+    }
+
+    #[test]
+    fn filter_drops_rows_with_errors() {
+        let x = Variable::new_unchecked("x");
+        let sm = solution_mappings(
+            vec![Series::new("x", [1i64, 0])],
+            vec![("x", RDFNodeType::Literal(xsd::INTEGER.into_owned()))],
+        );
+        let sm = with_literal(sm, integer(10), &context(0));
+        let sm = variable(sm, &x, &context(1)).unwrap();
+        let sm =
+            binary_expression(sm, Operator::Divide, &context(0), &context(1), &context(2)).unwrap();
+        let divide = Expression::Divide(
+            Box::new(Expression::Literal(integer(10))),
+            Box::new(Expression::Variable(x)),
+        );
+        assert_eq!(filtered_height(sm, divide, &context(2)), 1);
+
+        let sm = with_literal(rows(2), Literal::new_simple_literal("abc"), &context(0));
+        let sm = with_literal(sm, integer(1), &context(1));
+        let sm =
+            binary_expression(sm, Operator::Plus, &context(0), &context(1), &context(2)).unwrap();
+        let add = Expression::Add(
+            Box::new(Expression::Literal(Literal::new_simple_literal("abc"))),
+            Box::new(Expression::Literal(integer(1))),
+        );
+        assert_eq!(filtered_height(sm, add, &context(2)), 0);
+
+        let s = Variable::new_unchecked("s");
+        let sm = solution_mappings(
+            vec![Series::new("s", ["1", "x"])],
+            vec![("s", RDFNodeType::Literal(xsd::STRING.into_owned()))],
+        );
+        let sm = variable(sm, &s, &context(0)).unwrap();
+        let sm = cast_to_integer(sm, Expression::Variable(s.clone()));
+        let cast = Expression::FunctionCall(
+            Function::Custom(xsd::INTEGER.into_owned()),
+            vec![Expression::Variable(s)],
+        );
+        assert_eq!(filtered_height(sm, cast, &context(1)), 1);
+    }
+
+    #[test]
+    fn logical_operators_use_effective_boolean_values() {
+        let name = Variable::new_unchecked("name");
+        let count = Variable::new_unchecked("count");
+        let sm = || {
+            solution_mappings(
+                vec![
+                    Series::new("name", ["", "a", "b"]),
+                    Series::new("count", [1i64, 0, 2]),
+                ],
+                vec![
+                    ("name", RDFNodeType::Literal(xsd::STRING.into_owned())),
+                    ("count", RDFNodeType::Literal(xsd::INTEGER.into_owned())),
+                ],
+            )
+        };
+        let not = variable(sm(), &name, &context(0)).unwrap();
+        let not = not_expression(not, &context(0), &context(1)).unwrap();
+        let not_name = Expression::Not(Box::new(Expression::Variable(name.clone())));
+        assert_eq!(filtered_height(not, not_name, &context(1)), 1);
+
+        let and = variable(sm(), &name, &context(0)).unwrap();
+        let and = variable(and, &count, &context(1)).unwrap();
+        let and =
+            binary_expression(and, Operator::And, &context(0), &context(1), &context(2)).unwrap();
+        let name_and_count = Expression::And(
+            Box::new(Expression::Variable(name)),
+            Box::new(Expression::Variable(count)),
+        );
+        assert_eq!(filtered_height(and, name_and_count, &context(2)), 1);
+    }
+
+    #[test]
+    fn string_functions_accept_multi_typed_arguments() {
+        let sm = with_literal(
+            rows(1),
+            Literal::new_language_tagged_literal_unchecked("ab", "en"),
+            &context(0),
+        );
+        let sm = with_literal(
+            sm,
+            Literal::new_language_tagged_literal_unchecked("cd", "en"),
+            &context(1),
+        );
+        let args = vec![
+            Expression::Literal(Literal::new_language_tagged_literal_unchecked("ab", "en")),
+            Expression::Literal(Literal::new_language_tagged_literal_unchecked("cd", "en")),
+        ];
+        let sm = func_expression(
+            sm,
+            &Function::Concat,
+            &args,
+            HashMap::from([(0, context(0)), (1, context(1))]),
+            &context(2),
+            &QuerySettings::default(),
+        )
+        .unwrap();
+        let concatenated = vec![Expression::FunctionCall(Function::Concat, args)];
+        let sm = func_expression(
+            sm,
+            &Function::StrLen,
+            &concatenated,
+            HashMap::from([(0, context(2))]),
+            &context(3),
+            &QuerySettings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            sm.rdf_node_types.get(context(3).as_str()),
+            Some(&RDFNodeType::Literal(xsd::INTEGER.into_owned()))
+        );
+        let lengths = column(sm, &context(3));
+        assert_eq!(
+            lengths
+                .cast(&DataType::Int64)
+                .unwrap()
+                .i64()
+                .unwrap()
+                .get(0),
+            Some(4)
+        );
+    }
+
+    //Evaluates a function of literals, which are put in the contexts 0 and up, into the context
+    //after the arguments
+    fn try_call(
+        func: Function,
+        args: Vec<Literal>,
+        settings: &QuerySettings,
+    ) -> Result<(SolutionMappings, Context), QueryProcessingError> {
+        let mut sm = rows(1);
+        let mut args_contexts = HashMap::new();
+        for (i, arg) in args.iter().enumerate() {
+            sm = with_literal(sm, arg.clone(), &context(i as u16));
+            args_contexts.insert(i, context(i as u16));
+        }
+        let out = context(args.len() as u16);
+        let args = args.into_iter().map(Expression::Literal).collect();
+        let sm = func_expression(sm, &func, &args, args_contexts, &out, settings)?;
+        Ok((sm, out))
+    }
+
+    fn call(func: Function, args: Vec<Literal>) -> (RDFNodeType, Series) {
+        let (sm, out) = try_call(func, args, &QuerySettings::default()).unwrap();
+        let t = sm.rdf_node_types.get(out.as_str()).unwrap().clone();
+        (t, column(sm, &out))
+    }
+
+    fn struct_string_field(s: &Series, name: &str) -> Option<String> {
+        let field = s.struct_().unwrap().field_by_name(name).unwrap();
+        let value = field.str().unwrap().get(0);
+        value.map(|v| v.to_string())
+    }
+
+    fn tagged(value: &str, lang: &str) -> Literal {
+        Literal::new_language_tagged_literal_unchecked(value, lang)
+    }
+
+    #[test]
+    fn invalid_constant_patterns_and_replacements_are_errors() {
+        let simple = Literal::new_simple_literal;
+        let settings = QuerySettings::default();
+        let result = try_call(Function::Regex, vec![simple("a"), simple("(")], &settings);
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::InvalidRegex(..))
+        ));
+        let result = try_call(
+            Function::Regex,
+            vec![simple("a"), simple("a"), simple("q")],
+            &settings,
+        );
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::InvalidRegex(..))
+        ));
+        let result = try_call(
+            Function::Replace,
+            vec![simple("a"), simple("a"), simple("\\x")],
+            &settings,
+        );
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::InvalidRegex(..))
+        ));
+        let result = try_call(
+            Function::Replace,
+            vec![simple("a"), simple("(a)"), simple("$1")],
+            &settings,
+        );
+        assert!(result.is_ok());
+    }
+
+    //An integer where the column has a value and the string "a" elsewhere
+    fn integer_or_string(
+        mut sm: SolutionMappings,
+        values: &[Option<i64>],
+        c: &Context,
+    ) -> SolutionMappings {
+        let integers = context(10);
+        sm.mappings = sm
+            .mappings
+            .with_column(lit(Series::new(integers.as_str(), values)).alias(integers.as_str()));
+        sm.rdf_node_types.insert(
+            integers.as_str().to_string(),
+            RDFNodeType::Literal(xsd::INTEGER.into_owned()),
+        );
+        let sm = with_literal(sm, Literal::new_simple_literal("a"), &context(11));
+        coalesce_expression(sm, vec![integers, context(11)], c).unwrap()
+    }
+
+    #[test]
+    fn same_term_of_multi_types_compares_types_and_values() {
+        let sm = integer_or_string(rows(4), &[Some(1), None, Some(1), Some(1)], &context(0));
+        let sm = integer_or_string(sm, &[Some(1), None, None, Some(2)], &context(1));
+        for c in [context(0), context(1)] {
+            assert!(matches!(
+                sm.rdf_node_types.get(c.as_str()),
+                Some(RDFNodeType::MultiType(..))
+            ));
+        }
+        let sm = same_term(sm, &context(0), &context(1), &context(2)).unwrap();
+        let s = column(sm, &context(2));
+        let values: Vec<_> = s.bool().unwrap().into_iter().collect();
+        assert_eq!(
+            values,
+            vec![Some(true), Some(true), Some(false), Some(false)]
+        );
+    }
+
+    #[test]
+    fn same_term_of_language_tagged_strings_compares_tags() {
+        for (right, expected) in [
+            (tagged("a", "en"), true),
+            (tagged("a", "de"), false),
+            (Literal::new_simple_literal("a"), false),
+        ] {
+            let sm = with_literal(rows(1), tagged("a", "en"), &context(0));
+            let sm = with_literal(sm, right, &context(1));
+            let sm = same_term(sm, &context(0), &context(1), &context(2)).unwrap();
+            let s = column(sm, &context(2));
+            assert_eq!(s.bool().unwrap().get(0), Some(expected));
+        }
+    }
+
+    #[test]
+    fn datatype_of_a_language_tagged_string() {
+        let (t, s) = call(Function::Datatype, vec![tagged("a", "en")]);
+        assert_eq!(t, RDFNodeType::IRI);
+        let expected = named_node(rows(1), &rdf::LANG_STRING.into_owned(), &context(0)).unwrap();
+        assert_eq!(
+            s.get(0).unwrap(),
+            column(expected, &context(0)).get(0).unwrap()
+        );
+    }
+
+    #[test]
+    fn iri_is_resolved_against_the_base_iri() {
+        let relative = Literal::new_simple_literal("c/d");
+        let settings = QuerySettings::default()
+            .with_base_iri(NamedNode::new_unchecked("http://example.com/a/b"));
+        let (sm, out) = try_call(Function::Iri, vec![relative.clone()], &settings).unwrap();
+        assert_eq!(sm.rdf_node_types.get(out.as_str()), Some(&RDFNodeType::IRI));
+        let s = column(sm, &out);
+        assert_eq!(s.str().unwrap().get(0), Some("http://example.com/a/c/d"));
+        //Without a base only absolute IRIs are valid
+        let (_, s) = call(Function::Iri, vec![relative]);
+        assert_eq!(s.null_count(), 1);
+    }
+
+    #[test]
+    fn blank_nodes_are_unique_per_row_and_call_site() {
+        let settings = QuerySettings::default();
+        let mut sm = rows(3);
+        for c in [context(0), context(1)] {
+            sm = func_expression(sm, &Function::BNode, &vec![], HashMap::new(), &c, &settings)
+                .unwrap();
+        }
+        let df = sm.mappings.collect().unwrap();
+        let mut labels = vec![];
+        for c in [context(0), context(1)] {
+            let s = df.column(c.as_str()).unwrap();
+            labels.extend(s.str().unwrap().into_iter().map(|l| l.unwrap().to_string()));
+        }
+        let unique: std::collections::HashSet<_> = labels.iter().collect();
+        assert_eq!(unique.len(), 6);
+    }
+
+    //Calls a function without arguments in the contexts 0 and up of n rows
+    fn nullary_calls(func: Function, n: i64, calls: u16, settings: &QuerySettings) -> Vec<Series> {
+        let mut sm = rows(n);
+        for i in 0..calls {
+            sm =
+                func_expression(sm, &func, &vec![], HashMap::new(), &context(i), settings).unwrap();
+        }
+        let df = sm.mappings.collect().unwrap();
+        (0..calls)
+            .map(|i| df.column(context(i).as_str()).unwrap().clone())
+            .collect()
+    }
+
+    #[test]
+    fn uuids_are_distinct_per_row() {
+        for func in [Function::Uuid, Function::StrUuid] {
+            let s = nullary_calls(func, 5, 1, &QuerySettings::default()).remove(0);
+            assert_eq!(s.n_unique().unwrap(), 5);
+        }
+    }
+
+    #[test]
+    fn now_is_the_same_across_rows_and_calls() {
+        let settings = QuerySettings::default().for_query();
+        assert!(settings.now.is_some());
+        let calls = nullary_calls(Function::Now, 3, 2, &settings);
+        assert_eq!(calls[0].n_unique().unwrap(), 1);
+        assert!(calls[0].equals(&calls[1]));
+    }
+
+    #[test]
+    fn seeded_random_values_are_reproducible() {
+        for func in [Function::Rand, Function::Uuid] {
+            let seeded = QuerySettings::default().with_seed(42);
+            let first = nullary_calls(func.clone(), 4, 2, &seeded);
+            let second = nullary_calls(func.clone(), 4, 2, &seeded);
+            assert!(first[0].equals(&second[0]));
+            assert!(first[1].equals(&second[1]));
+            //Rows and call sites still get different values
+            assert_eq!(first[0].n_unique().unwrap(), 4);
+            assert!(!first[0].equals(&first[1]));
+            let other = QuerySettings::default().with_seed(43);
+            assert!(!first[0].equals(&nullary_calls(func, 4, 1, &other)[0]));
+        }
+    }
+
+    #[test]
+    fn strdt_parses_the_lexical_form() {
+        let datatype = NamedNode::from(xsd::INTEGER);
+        let sm = with_literal(rows(1), Literal::new_simple_literal("12"), &context(0));
+        let sm = named_node(sm, &datatype, &context(1)).unwrap();
+        let args = vec![
+            Expression::Literal(Literal::new_simple_literal("12")),
+            Expression::NamedNode(datatype.clone()),
+        ];
+        let args_contexts = HashMap::from([(0, context(0)), (1, context(1))]);
+        let sm = func_expression(
+            sm,
+            &Function::StrDt,
+            &args,
+            args_contexts,
+            &context(2),
+            &QuerySettings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            sm.rdf_node_types.get(context(2).as_str()),
+            Some(&RDFNodeType::Literal(datatype))
+        );
+        let s = column(sm, &context(2));
+        assert_eq!(
+            s.cast(&DataType::Int64).unwrap().i64().unwrap().get(0),
+            Some(12)
+        );
+    }
+
+    #[test]
+    fn strdt_with_a_variable_datatype_is_unbound() {
+        let sm = with_literal(rows(1), Literal::new_simple_literal("12"), &context(0));
+        let sm = named_node(sm, &NamedNode::from(xsd::INTEGER), &context(1)).unwrap();
+        let args = vec![
+            Expression::Literal(Literal::new_simple_literal("12")),
+            Expression::Variable(Variable::new_unchecked("dt")),
+        ];
+        let args_contexts = HashMap::from([(0, context(0)), (1, context(1))]);
+        let sm = func_expression(
+            sm,
+            &Function::StrDt,
+            &args,
+            args_contexts,
+            &context(2),
+            &QuerySettings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            sm.rdf_node_types.get(context(2).as_str()),
+            Some(&RDFNodeType::None)
+        );
+        assert_eq!(column(sm, &context(2)).null_count(), 1);
+    }
+
+    #[test]
+    fn strbefore_and_strafter_keep_the_language_tag_when_found() {
+        let string_field = base_col_name(&BaseRDFNodeType::Literal(xsd::STRING.into_owned()));
+        for (func, expected) in [(Function::StrBefore, "a"), (Function::StrAfter, "c")] {
+            let (t, s) = call(
+                func.clone(),
+                vec![tagged("abc", "en"), Literal::new_simple_literal("b")],
+            );
+            assert!(matches!(t, RDFNodeType::MultiType(..)));
+            assert_eq!(
+                struct_string_field(&s, LANG_STRING_VALUE_FIELD),
+                Some(expected.to_string())
+            );
+            assert_eq!(
+                struct_string_field(&s, LANG_STRING_LANG_FIELD),
+                Some("en".to_string())
+            );
+            assert_eq!(struct_string_field(&s, &string_field), None);
+
+            //Not finding the second argument gives an empty simple literal
+            let (_, s) = call(
+                func.clone(),
+                vec![tagged("abc", "en"), Literal::new_simple_literal("z")],
+            );
+            assert_eq!(struct_string_field(&s, LANG_STRING_VALUE_FIELD), None);
+            assert_eq!(struct_string_field(&s, &string_field), Some("".to_string()));
+
+            let (_, s) = call(func.clone(), vec![tagged("abc", "en"), tagged("b", "en")]);
+            assert_eq!(
+                struct_string_field(&s, LANG_STRING_VALUE_FIELD),
+                Some(expected.to_string())
+            );
+
+            //Different language tags are incompatible
+            let (_, s) = call(func, vec![tagged("abc", "en"), tagged("b", "fr")]);
+            assert_eq!(struct_string_field(&s, LANG_STRING_VALUE_FIELD), None);
+            assert_eq!(struct_string_field(&s, &string_field), None);
+        }
+    }
+
+    #[test]
+    fn strbefore_of_simple_literals_is_a_simple_literal() {
+        let (t, s) = call(
+            Function::StrBefore,
+            vec![
+                Literal::new_simple_literal("abc"),
+                Literal::new_simple_literal("c"),
+            ],
+        );
+        assert_eq!(t, RDFNodeType::Literal(xsd::STRING.into_owned()));
+        assert_eq!(s.str().unwrap().get(0), Some("ab"));
+    }
+
+    fn typed_variable(values: Series, datatype: NamedNodeRef) -> SolutionMappings {
+        let name = values.name().to_string();
+        let sm = solution_mappings(
+            vec![values],
+            vec![(name.as_str(), RDFNodeType::Literal(datatype.into_owned()))],
+        );
+        variable(sm, &Variable::new_unchecked(name), &context(0)).unwrap()
+    }
+
+    fn unary(solution_mappings: SolutionMappings, minus: bool) -> (RDFNodeType, Series) {
+        let sm = if minus {
+            unary_minus(solution_mappings, &context(0), &context(1)).unwrap()
+        } else {
+            unary_plus(solution_mappings, &context(0), &context(1)).unwrap()
+        };
+        let t = sm.rdf_node_types.get(context(1).as_str()).unwrap().clone();
+        (t, column(sm, &context(1)))
+    }
+
+    fn int64_values(s: &Series) -> Vec<Option<i64>> {
+        s.cast(&DataType::Int64)
+            .unwrap()
+            .i64()
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+
+    fn float64_values(s: &Series) -> Vec<Option<f64>> {
+        s.f64().unwrap().into_iter().collect()
+    }
+
+    #[test]
+    fn zoned_minus_local_datetime_is_unbound() {
+        let instants = Series::new("t", [0i64, 3_600_000_000_000]);
+        let zoned = instants
+            .cast(&DataType::Datetime(
+                TimeUnit::Nanoseconds,
+                Some("UTC".into()),
+            ))
+            .unwrap();
+        let local = instants
+            .cast(&DataType::Datetime(TimeUnit::Nanoseconds, None))
+            .unwrap();
+        let datetime = RDFNodeType::Literal(xsd::DATE_TIME.into_owned());
+        for (left, right, expected) in [
+            (zoned.clone(), zoned.clone(), vec![Some(0), Some(0)]),
+            (local.clone(), zoned.clone(), vec![None, None]),
+            (zoned, local, vec![None, None]),
+        ] {
+            let sm = solution_mappings(
+                vec![
+                    left.with_name(context(0).as_str()),
+                    right.with_name(context(1).as_str()),
+                ],
+                vec![
+                    (context(0).as_str(), datetime.clone()),
+                    (context(1).as_str(), datetime.clone()),
+                ],
+            );
+            let sm = binary_expression(sm, Operator::Minus, &context(0), &context(1), &context(2))
+                .unwrap();
+            assert_eq!(int64_values(&column(sm, &context(2))), expected);
+        }
+    }
+
+    #[test]
+    fn unary_minus_keeps_signed_numeric_types() {
+        let (t, s) = unary(typed_variable(Series::new("v", [1i32, -2]), xsd::INT), true);
+        assert_eq!(t, RDFNodeType::Literal(xsd::INT.into_owned()));
+        assert_eq!(s.dtype(), &DataType::Int32);
+        assert_eq!(int64_values(&s), vec![Some(-1), Some(2)]);
+
+        let decimal = typed_variable(Series::new("v", [1.5f64]), xsd::DECIMAL);
+        let (t, s) = unary(decimal, true);
+        assert_eq!(t, RDFNodeType::Literal(xsd::DECIMAL.into_owned()));
+        assert_eq!(float64_values(&s), vec![Some(-1.5)]);
+
+        let double = typed_variable(Series::new("v", [2.5f64]), xsd::DOUBLE);
+        let (t, s) = unary(double, true);
+        assert_eq!(t, RDFNodeType::Literal(xsd::DOUBLE.into_owned()));
+        assert_eq!(float64_values(&s), vec![Some(-2.5)]);
+    }
+
+    #[test]
+    fn unary_minus_of_unsigned_integers_gives_integers() {
+        let unsigned = typed_variable(Series::new("v", [1u32, 2]), xsd::UNSIGNED_INT);
+        let (t, s) = unary(unsigned, true);
+        assert_eq!(t, RDFNodeType::Literal(xsd::INTEGER.into_owned()));
+        assert_eq!(s.dtype(), &DataType::Int64);
+        assert_eq!(int64_values(&s), vec![Some(-1), Some(-2)]);
+    }
+
+    #[test]
+    fn unary_minus_negates_durations() {
+        let hour = Literal::new_typed_literal("PT1H", xsd::DAY_TIME_DURATION);
+        let (t, s) = unary(with_literal(rows(1), hour, &context(0)), true);
+        assert_eq!(t, RDFNodeType::Literal(xsd::DAY_TIME_DURATION.into_owned()));
+        assert_eq!(s.dtype(), &DataType::Duration(TimeUnit::Nanoseconds));
+        assert_eq!(int64_values(&s), vec![Some(-3_600_000_000_000)]);
+
+        let months = Literal::new_typed_literal("P1Y2M", xsd::YEAR_MONTH_DURATION);
+        let (t, s) = unary(with_literal(rows(1), months, &context(0)), true);
+        assert_eq!(
+            t,
+            RDFNodeType::Literal(xsd::YEAR_MONTH_DURATION.into_owned())
+        );
+        assert_eq!(int64_values(&s), vec![Some(-14)]);
+    }
+
+    #[test]
+    fn unary_plus_keeps_the_type() {
+        let (t, s) = unary(typed_variable(Series::new("v", [1i32]), xsd::INT), false);
+        assert_eq!(t, RDFNodeType::Literal(xsd::INT.into_owned()));
+        assert_eq!(int64_values(&s), vec![Some(1)]);
+
+        let unsigned = typed_variable(Series::new("v", [1u32]), xsd::UNSIGNED_INT);
+        let (t, _) = unary(unsigned, false);
+        assert_eq!(t, RDFNodeType::Literal(xsd::UNSIGNED_INT.into_owned()));
+    }
+
+    #[test]
+    fn unary_operators_on_strings_and_multi_types_are_unbound() {
+        for minus in [true, false] {
+            let string = Literal::new_simple_literal("a");
+            let (t, s) = unary(with_literal(rows(1), string, &context(0)), minus);
+            assert_eq!(t, RDFNodeType::None);
+            assert_eq!(s.null_count(), 1);
+
+            let sm = with_literal(rows(1), integer(1), &context(2));
+            let sm = with_literal(sm, Literal::new_simple_literal("a"), &context(3));
+            let sm = coalesce_expression(sm, vec![context(2), context(3)], &context(0)).unwrap();
+            assert!(matches!(
+                sm.rdf_node_types.get(context(0).as_str()),
+                Some(RDFNodeType::MultiType(..))
+            ));
+            let (t, s) = unary(sm, minus);
+            assert_eq!(t, RDFNodeType::None);
+            assert_eq!(s.null_count(), 1);
+        }
+    }
+
+    #[test]
+    fn digests_of_non_simple_literals_are_unbound() {
+        let tagged = Literal::new_language_tagged_literal_unchecked("abc", "en");
+        for arg in [tagged, integer(1)] {
+            for func in [
+                Function::Md5,
+                Function::Sha1,
+                Function::Sha256,
+                Function::Sha384,
+                Function::Sha512,
+            ] {
+                let sm = with_literal(rows(1), arg.clone(), &context(0));
+                let sm = func_expression(
+                    sm,
+                    &func,
+                    &vec![Expression::Literal(arg.clone())],
+                    HashMap::from([(0, context(0))]),
+                    &context(1),
+                    &QuerySettings::default(),
+                )
+                .unwrap();
+                assert_eq!(
+                    sm.rdf_node_types.get(context(1).as_str()),
+                    Some(&RDFNodeType::None)
+                );
+                assert_eq!(column(sm, &context(1)).null_count(), 1);
+            }
+        }
+    }
+
+    //Adds dateTime values parsed from their lexical forms in the context
+    fn with_datetimes(mut sm: SolutionMappings, values: &[&str], c: &Context) -> SolutionMappings {
+        let string = RDFNodeType::Literal(xsd::STRING.into_owned());
+        let lexical_forms = lit(Series::new(c.as_str(), values));
+        sm.mappings = sm
+            .mappings
+            .with_column(xsd_cast(lexical_forms, &string, xsd::DATE_TIME).alias(c.as_str()));
+        sm.rdf_node_types.insert(
+            c.as_str().to_string(),
+            RDFNodeType::Literal(xsd::DATE_TIME.into_owned()),
+        );
+        sm
+    }
+
+    fn datetime_variable(values: &[&str]) -> SolutionMappings {
+        with_datetimes(rows(values.len() as i64), values, &context(0))
+    }
+
+    //Applies the operator to the value in the context 0 and a literal
+    fn operate(sm: SolutionMappings, op: Operator, operand: Literal) -> (RDFNodeType, Series) {
+        let sm = with_literal(sm, operand, &context(1));
+        let sm = binary_expression(sm, op, &context(0), &context(1), &context(2)).unwrap();
+        let t = sm.rdf_node_types.get(context(2).as_str()).unwrap().clone();
+        (t, column(sm, &context(2)))
+    }
+
+    //As operate, giving the lexical forms of the result
+    fn with_operand(
+        sm: SolutionMappings,
+        op: Operator,
+        operand: Literal,
+    ) -> (RDFNodeType, Vec<Option<String>>) {
+        let (t, s) = operate(sm, op, operand);
+        let df = DataFrame::new(vec![s.with_name("out")]).unwrap();
+        let df = df
+            .lazy()
+            .select([xsd_cast(col("out"), &t, xsd::STRING)])
+            .collect()
+            .unwrap();
+        let strings = df.column("out").unwrap().str().unwrap().clone();
+        let strings = strings
+            .into_iter()
+            .map(|x| x.map(|x| x.to_string()))
+            .collect();
+        (t, strings)
+    }
+
+    fn some_strings(values: &[&str]) -> Vec<Option<String>> {
+        values.iter().map(|x| Some(x.to_string())).collect()
+    }
+
+    #[test]
+    fn durations_mixing_months_and_days_are_unbound() {
+        let mixed = Literal::new_typed_literal("P1MT1H", xsd::DURATION);
+        let sm = with_literal(rows(1), mixed.clone(), &context(0));
+        assert_eq!(
+            sm.rdf_node_types.get(context(0).as_str()),
+            Some(&RDFNodeType::None)
+        );
+        assert_eq!(column(sm, &context(0)).null_count(), 1);
+        let sm = datetime_variable(&["2020-01-01T00:00:00Z"]);
+        let sm = with_literal(sm, mixed, &context(1));
+        let sm =
+            binary_expression(sm, Operator::Plus, &context(0), &context(1), &context(2)).unwrap();
+        assert_eq!(
+            sm.rdf_node_types.get(context(2).as_str()),
+            Some(&RDFNodeType::None)
+        );
+    }
+
+    #[test]
+    fn adding_a_day_time_duration_keeps_the_offset() {
+        let quarter = Literal::new_typed_literal("PT15M", xsd::DURATION);
+        let values = [
+            "2020-01-01T23:50:00Z",
+            "2020-01-01T00:00:00+05:30",
+            "2020-01-01T00:00:00",
+        ];
+        let (t, strings) = with_operand(datetime_variable(&values), Operator::Plus, quarter);
+        assert_eq!(t, RDFNodeType::Literal(xsd::DATE_TIME.into_owned()));
+        assert_eq!(
+            strings,
+            some_strings(&[
+                "2020-01-02T00:05:00Z",
+                "2020-01-01T00:15:00+05:30",
+                "2020-01-01T00:15:00"
+            ])
+        );
+        let hour = Literal::new_typed_literal("PT1H", xsd::DAY_TIME_DURATION);
+        let sm = datetime_variable(&["2020-01-01T00:00:00+02:00"]);
+        let (_, strings) = with_operand(sm, Operator::Minus, hour);
+        assert_eq!(strings, some_strings(&["2019-12-31T23:00:00+02:00"]));
+    }
+
+    #[test]
+    fn datetime_minus_datetime_is_a_day_time_duration() {
+        let sm = datetime_variable(&["2020-01-01T00:00:00+05:30", "2020-01-02T00:00:00Z"]);
+        let sm = with_datetimes(sm, &["2019-12-31T18:00:00Z"; 2], &context(1));
+        let sm =
+            binary_expression(sm, Operator::Minus, &context(0), &context(1), &context(2)).unwrap();
+        let t = sm.rdf_node_types.get(context(2).as_str()).unwrap().clone();
+        let s = column(sm, &context(2));
+        assert_eq!(t, RDFNodeType::Literal(xsd::DAY_TIME_DURATION.into_owned()));
+        assert_eq!(
+            int64_values(&s),
+            vec![Some(1_800_000_000_000), Some(108_000_000_000_000)]
+        );
+    }
+
+    #[test]
+    fn adding_months_keeps_the_local_time() {
+        let month = Literal::new_typed_literal("P1M", xsd::YEAR_MONTH_DURATION);
+        let values = [
+            "2020-01-31T10:00:00Z",
+            "2020-01-31T10:00:00+05:30",
+            "2020-01-31T10:00:00",
+        ];
+        let (t, strings) = with_operand(datetime_variable(&values), Operator::Plus, month.clone());
+        assert_eq!(t, RDFNodeType::Literal(xsd::DATE_TIME.into_owned()));
+        assert_eq!(
+            strings,
+            some_strings(&[
+                "2020-02-29T10:00:00Z",
+                "2020-02-29T10:00:00+05:30",
+                "2020-02-29T10:00:00"
+            ])
+        );
+        let (_, strings) = with_operand(
+            datetime_variable(&["2020-03-31T10:00:00-09:30"]),
+            Operator::Minus,
+            month.clone(),
+        );
+        assert_eq!(strings, some_strings(&["2020-02-29T10:00:00-09:30"]));
+        //Named timezones take the offset of the new local time
+        let mut sm = datetime_variable(&["2020-03-01T11:00:00Z"]);
+        sm.mappings = sm
+            .mappings
+            .with_column(col(context(0).as_str()).cast(DataType::Datetime(
+                TimeUnit::Nanoseconds,
+                Some("Europe/Oslo".to_string()),
+            )));
+        let (_, strings) = with_operand(sm, Operator::Plus, month);
+        assert_eq!(strings, some_strings(&["2020-04-01T12:00:00+02:00"]));
+    }
+
+    #[test]
+    fn durations_are_scaled_by_numbers() {
+        let hour = Literal::new_typed_literal("PT1H", xsd::DAY_TIME_DURATION);
+        let factor = Literal::new_typed_literal("2.5", xsd::DOUBLE);
+        for (left, right) in [(hour.clone(), factor.clone()), (factor, hour.clone())] {
+            let sm = with_literal(rows(1), left, &context(0));
+            let (t, s) = operate(sm, Operator::Multiply, right);
+            assert_eq!(t, RDFNodeType::Literal(xsd::DAY_TIME_DURATION.into_owned()));
+            assert_eq!(int64_values(&s), vec![Some(9_000_000_000_000)]);
+        }
+        let year = Literal::new_typed_literal("P1Y", xsd::YEAR_MONTH_DURATION);
+        let sm = with_literal(rows(1), year, &context(0));
+        let (t, s) = operate(
+            sm,
+            Operator::Multiply,
+            Literal::new_typed_literal("1.5", xsd::DECIMAL),
+        );
+        assert_eq!(
+            t,
+            RDFNodeType::Literal(xsd::YEAR_MONTH_DURATION.into_owned())
+        );
+        assert_eq!(int64_values(&s), vec![Some(18)]);
+        let sm = with_literal(rows(1), hour, &context(0));
+        let (_, s) = operate(sm, Operator::Divide, integer(0));
+        assert_eq!(s.null_count(), 1);
+    }
+
+    fn datetime_function(func: Function, values: &[&str]) -> (RDFNodeType, Series) {
+        let args = vec![Expression::Variable(Variable::new_unchecked("t"))];
+        let sm = func_expression(
+            datetime_variable(values),
+            &func,
+            &args,
+            HashMap::from([(0, context(0))]),
+            &context(1),
+            &QuerySettings::default(),
+        )
+        .unwrap();
+        let t = sm.rdf_node_types.get(context(1).as_str()).unwrap().clone();
+        (t, column(sm, &context(1)))
+    }
+
+    #[test]
+    fn seconds_are_decimals_in_local_time() {
+        let values = ["2020-01-01T10:15:30.25+05:30", "2020-01-01T10:15:07Z"];
+        let (t, s) = datetime_function(Function::Seconds, &values);
+        assert_eq!(t, RDFNodeType::Literal(xsd::DECIMAL.into_owned()));
+        assert_eq!(float64_values(&s), vec![Some(30.25), Some(7.0)]);
+        let (t, s) = datetime_function(Function::Hours, &values);
+        assert_eq!(t, RDFNodeType::Literal(xsd::INTEGER.into_owned()));
+        assert_eq!(int64_values(&s), vec![Some(10), Some(10)]);
+    }
+
+    #[test]
+    fn components_of_gregorian_values_with_timezones() {
+        for (value, datatype, func, expected) in [
+            ("2020Z", xsd::G_YEAR, Function::Year, 2020),
+            ("-0044+02:00", xsd::G_YEAR, Function::Year, -44),
+            ("2024-05Z", xsd::G_YEAR_MONTH, Function::Year, 2024),
+            ("2024-05Z", xsd::G_YEAR_MONTH, Function::Month, 5),
+        ] {
+            let (t, s) = call(func, vec![Literal::new_typed_literal(value, datatype)]);
+            assert_eq!(t, RDFNodeType::Literal(xsd::INTEGER.into_owned()));
+            assert_eq!(int64_values(&s), vec![Some(expected)]);
+        }
+    }
+
+    #[test]
+    fn round_rounds_halves_upwards() {
+        for (value, expected) in [(-2.5, -2.0), (2.5, 3.0), (-2.6, -3.0), (2.4, 2.0)] {
+            let (t, s) = call(Function::Round, vec![Literal::from(value)]);
+            assert_eq!(t, RDFNodeType::Literal(xsd::DOUBLE.into_owned()));
+            assert_eq!(float64_values(&s), vec![Some(expected)]);
+        }
+        let decimal = Literal::new_typed_literal("-2.5", xsd::DECIMAL);
+        let (t, s) = call(Function::Round, vec![decimal]);
+        assert_eq!(t, RDFNodeType::Literal(xsd::DECIMAL.into_owned()));
+        assert_eq!(float64_values(&s), vec![Some(-2.0)]);
+    }
 }
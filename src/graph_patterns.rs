@@ -1,4 +1,7 @@
 use crate::errors::QueryProcessingError;
+use crate::expressions::effective_boolean_value;
+use crate::settings::QuerySettings;
+use crate::type_inference::infer_expression_types;
 use log::warn;
 use oxrdf::Variable;
 use polars::datatypes::{CategoricalOrdering, DataType};
@@ -12,6 +15,7 @@ use representation::multitype::{join_workaround, unique_workaround};
 use representation::query_context::Context;
 use representation::solution_mapping::{is_string_col, SolutionMappings};
 use representation::{BaseRDFNodeType, RDFNodeType};
+use spargebra::algebra::Expression;
 use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
@@ -30,11 +34,34 @@ pub fn distinct(
     Ok(solution_mappings)
 }
 
+//Type errors in FILTER, BIND and ORDER BY expressions give unbound values, so they are reported as
+//warnings. The other errors found by type inference fail the query.
+fn check_expression(
+    solution_mappings: &SolutionMappings,
+    expression: &Expression,
+    expression_context: &Context,
+    settings: &QuerySettings,
+) -> Result<(), QueryProcessingError> {
+    let inferred = infer_expression_types(
+        expression,
+        &solution_mappings.rdf_node_types,
+        &settings.functions,
+        expression_context,
+    )?;
+    for e in inferred.type_errors {
+        warn!("{}", e);
+    }
+    Ok(())
+}
+
 pub fn extend(
     mut solution_mappings: SolutionMappings,
+    expression: &Expression,
     expression_context: &Context,
     variable: &Variable,
+    settings: &QuerySettings,
 ) -> Result<SolutionMappings, QueryProcessingError> {
+    check_expression(&solution_mappings, expression, expression_context, settings)?;
     solution_mappings.mappings = solution_mappings
         .mappings
         .rename([expression_context.as_str()], [variable.as_str()]);
@@ -50,11 +77,23 @@ pub fn extend(
 
 pub fn filter(
     mut solution_mappings: SolutionMappings,
+    expression: &Expression,
     expression_context: &Context,
+    settings: &QuerySettings,
 ) -> Result<SolutionMappings, QueryProcessingError> {
+    check_expression(&solution_mappings, expression, expression_context, settings)?;
+    let expression_type = solution_mappings
+        .rdf_node_types
+        .remove(expression_context.as_str())
+        .ok_or_else(|| {
+            QueryProcessingError::DatatypeNotFound(expression_context.as_str().to_string())
+        })?;
     solution_mappings.mappings = solution_mappings
         .mappings
-        .filter(col(expression_context.as_str()))
+        .filter(effective_boolean_value(
+            col(expression_context.as_str()),
+            &expression_type,
+        ))
         .drop([&expression_context.as_str()]);
     Ok(solution_mappings)
 }
@@ -194,9 +233,14 @@ pub fn minus(
 
 pub fn order_by(
     solution_mappings: SolutionMappings,
+    expressions: &[Expression],
     inner_contexts: &Vec<Context>,
     asc_ordering: Vec<bool>,
+    settings: &QuerySettings,
 ) -> Result<SolutionMappings, QueryProcessingError> {
+    for (expression, context) in expressions.iter().zip(inner_contexts) {
+        check_expression(&solution_mappings, expression, context, settings)?;
+    }
     let SolutionMappings {
         mut mappings,
         rdf_node_types: datatypes,
@@ -275,49 +319,10 @@ pub fn union(
         for (right_col, right_type) in right_datatypes {
             if let Some(left_type) = target_types.get(right_col) {
                 if left_type != right_type {
-                    if let RDFNodeType::MultiType(left_types) = left_type {
-                        let mut left_set: HashSet<_> = left_types.iter().collect();
-                        if let RDFNodeType::MultiType(right_types) = right_type {
-                            let right_set: HashSet<_> = right_types.iter().collect();
-                            let mut union: Vec<_> = left_set
-                                .union(&right_set)
-                                .into_iter()
-                                .map(|x| (*x).clone())
-                                .collect();
-                            union.sort();
-                            updated_target_types
-                                .insert(right_col.clone(), RDFNodeType::MultiType(union));
-                        } else {
-                            //Right not multi
-                            let base_right = BaseRDFNodeType::from_rdf_node_type(right_type);
-                            left_set.insert(&base_right);
-                            let mut new_types: Vec<_> =
-                                left_set.into_iter().map(|x| x.clone()).collect();
-                            new_types.sort();
-                            let new_type = RDFNodeType::MultiType(new_types);
-                            updated_target_types.insert(right_col.clone(), new_type);
-                        }
-                    } else {
-                        //Left not multi
-                        if let RDFNodeType::MultiType(right_types) = right_type {
-                            let mut right_set: HashSet<_> = right_types.iter().collect();
-                            let base_left = BaseRDFNodeType::from_rdf_node_type(left_type);
-                            right_set.insert(&base_left);
-                            let mut new_types: Vec<_> =
-                                right_set.into_iter().map(|x| x.clone()).collect();
-                            new_types.sort();
-                            let new_type = RDFNodeType::MultiType(new_types);
-                            updated_target_types.insert(right_col.clone(), new_type);
-                        } else {
-                            //Both not multi
-                            let base_left = BaseRDFNodeType::from_rdf_node_type(left_type);
-                            let base_right = BaseRDFNodeType::from_rdf_node_type(right_type);
-                            let mut new_types = vec![base_left.clone(), base_right.clone()];
-                            new_types.sort();
-                            let new_type = RDFNodeType::MultiType(new_types);
-                            updated_target_types.insert(right_col.to_string(), new_type);
-                        }
-                    }
+                    updated_target_types.insert(
+                        right_col.clone(),
+                        union_rdf_node_types(left_type, right_type),
+                    );
                 }
             } else {
                 updated_target_types.insert(right_col.clone(), right_type.clone());
@@ -372,8 +377,50 @@ pub fn union(
         }
     }
 
-    let mut output_mappings =
-        concat_lf_diagonal(to_concat, UnionArgs::default()).expect("Concat problem");
+    let mut output_mappings = concat_lf_diagonal(to_concat, UnionArgs::default())?;
     output_mappings = implode_multicolumns(output_mappings, exploded_map);
     Ok(SolutionMappings::new(output_mappings, target_types))
 }
+
+pub fn union_rdf_node_types(left_type: &RDFNodeType, right_type: &RDFNodeType) -> RDFNodeType {
+    if left_type == right_type {
+        return left_type.clone();
+    }
+    if let RDFNodeType::MultiType(left_types) = left_type {
+        let mut left_set: HashSet<_> = left_types.iter().collect();
+        if let RDFNodeType::MultiType(right_types) = right_type {
+            let right_set: HashSet<_> = right_types.iter().collect();
+            let mut union: Vec<_> = left_set
+                .union(&right_set)
+                .into_iter()
+                .map(|x| (*x).clone())
+                .collect();
+            union.sort();
+            RDFNodeType::MultiType(union)
+        } else {
+            //Right not multi
+            let base_right = BaseRDFNodeType::from_rdf_node_type(right_type);
+            left_set.insert(&base_right);
+            let mut new_types: Vec<_> = left_set.into_iter().map(|x| x.clone()).collect();
+            new_types.sort();
+            RDFNodeType::MultiType(new_types)
+        }
+    } else {
+        //Left not multi
+        if let RDFNodeType::MultiType(right_types) = right_type {
+            let mut right_set: HashSet<_> = right_types.iter().collect();
+            let base_left = BaseRDFNodeType::from_rdf_node_type(left_type);
+            right_set.insert(&base_left);
+            let mut new_types: Vec<_> = right_set.into_iter().map(|x| x.clone()).collect();
+            new_types.sort();
+            RDFNodeType::MultiType(new_types)
+        } else {
+            //Both not multi
+            let base_left = BaseRDFNodeType::from_rdf_node_type(left_type);
+            let base_right = BaseRDFNodeType::from_rdf_node_type(right_type);
+            let mut new_types = vec![base_left.clone(), base_right.clone()];
+            new_types.sort();
+            RDFNodeType::MultiType(new_types)
+        }
+    }
+}
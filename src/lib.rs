@@ -1,6 +1,16 @@
 pub mod aggregates;
+pub mod broadcasting;
+pub mod casts;
 pub mod constants;
+pub mod custom_functions;
+pub mod datetime_functions;
 pub mod errors;
 pub mod exists_helper;
 pub mod expressions;
 pub mod graph_patterns;
+pub mod math_functions;
+pub mod settings;
+pub mod string_functions;
+pub mod term_functions;
+pub mod type_inference;
+pub mod type_promotion;
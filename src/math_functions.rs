@@ -0,0 +1,484 @@
+use crate::broadcasting::broadcast_index;
+use crate::constants::MATH_NAMESPACE;
+use crate::custom_functions::{CustomFunction, CustomFunctionRegistry};
+use crate::errors::QueryProcessingError;
+use crate::type_inference::is_numeric_type;
+use crate::type_promotion::{is_integer_type, numeric_polars_dtype};
+use oxrdf::vocab::xsd;
+use oxrdf::NamedNodeRef;
+use polars::datatypes::DataType;
+use polars::prelude::{
+    lit, map_multiple, when, Expr, Float64Chunked, GetOutput, Int64Chunked, IntoSeries,
+    LiteralValue, PolarsResult, Series,
+};
+use representation::RDFNodeType;
+use spargebra::algebra::Expression;
+use std::f64::consts::{E, PI};
+use std::sync::Arc;
+
+#[derive(Clone, Copy)]
+enum MathOperation {
+    Sqrt,
+    Pow,
+    Exp,
+    Log,
+    Log10,
+    Sin,
+    Cos,
+    Tan,
+    Atan2,
+    Pi,
+}
+
+struct MathFunction {
+    iri: String,
+    operation: MathOperation,
+}
+
+pub fn register_math_functions(registry: &mut CustomFunctionRegistry) {
+    //ln is kept as an alias of the XPath name log
+    for (name, operation) in [
+        ("sqrt", MathOperation::Sqrt),
+        ("pow", MathOperation::Pow),
+        ("exp", MathOperation::Exp),
+        ("log", MathOperation::Log),
+        ("ln", MathOperation::Log),
+        ("log10", MathOperation::Log10),
+        ("sin", MathOperation::Sin),
+        ("cos", MathOperation::Cos),
+        ("tan", MathOperation::Tan),
+        ("atan2", MathOperation::Atan2),
+        ("pi", MathOperation::Pi),
+    ] {
+        registry.register(Arc::new(MathFunction {
+            iri: format!("{}{}", MATH_NAMESPACE, name),
+            operation,
+        }));
+    }
+}
+
+impl CustomFunction for MathFunction {
+    fn iri(&self) -> &str {
+        &self.iri
+    }
+
+    fn arity(&self) -> usize {
+        match self.operation {
+            MathOperation::Pi => 0,
+            MathOperation::Pow | MathOperation::Atan2 => 2,
+            _ => 1,
+        }
+    }
+
+    fn output_type(&self, arg_types: &[RDFNodeType]) -> Option<RDFNodeType> {
+        arg_types
+            .iter()
+            .all(is_numeric_type)
+            .then(|| RDFNodeType::Literal(xsd::DOUBLE.into_owned()))
+    }
+
+    fn expression(
+        &self,
+        args: Vec<Expr>,
+        _arg_types: &[RDFNodeType],
+        _sparql_args: &[Expression],
+    ) -> Result<Expr, QueryProcessingError> {
+        let args: Vec<_> = args
+            .into_iter()
+            .map(|a| a.cast(DataType::Float64))
+            .collect();
+        let expr = match self.operation {
+            MathOperation::Sqrt => args[0].clone().sqrt(),
+            MathOperation::Pow => args[0].clone().pow(args[1].clone()),
+            MathOperation::Exp => args[0].clone().exp(),
+            MathOperation::Log => args[0].clone().log(E),
+            MathOperation::Log10 => args[0].clone().log(10.0),
+            MathOperation::Sin => args[0].clone().sin(),
+            MathOperation::Cos => args[0].clone().cos(),
+            MathOperation::Tan => args[0].clone().tan(),
+            MathOperation::Atan2 => args[0].clone().arctan2(args[1].clone()),
+            MathOperation::Pi => return Ok(lit(PI)),
+        };
+        //Domain errors such as the square root of a negative number give NaN, which is unbound
+        Ok(when(expr.clone().is_nan())
+            .then(Expr::Literal(LiteralValue::Null))
+            .otherwise(expr))
+    }
+}
+
+//Pairs up the values of two series
+fn zip_broadcast<L: Copy, R: Copy>(
+    left: &[Option<L>],
+    right: &[Option<R>],
+) -> Vec<(Option<L>, Option<R>)> {
+    let len = left.len().max(right.len());
+    (0..len)
+        .map(|i| {
+            (
+                left.get(broadcast_index(left.len(), i)).copied().flatten(),
+                right
+                    .get(broadcast_index(right.len(), i))
+                    .copied()
+                    .flatten(),
+            )
+        })
+        .collect()
+}
+
+fn int_values(s: &Series) -> PolarsResult<Vec<Option<i64>>> {
+    Ok(s.cast(&DataType::Int64)?.i64()?.into_iter().collect())
+}
+
+fn float_values(s: &Series) -> PolarsResult<Vec<Option<f64>>> {
+    Ok(s.cast(&DataType::Float64)?.f64()?.into_iter().collect())
+}
+
+//The remainder has the sign of the dividend, a zero divisor is an error for integers and
+//decimals and gives NaN for floats and doubles
+pub fn numeric_mod(left: Expr, right: Expr, promoted: NamedNodeRef) -> Expr {
+    let dtype = numeric_polars_dtype(promoted);
+    let output = dtype.clone();
+    let integer = is_integer_type(promoted);
+    let decimal = promoted == xsd::DECIMAL;
+    map_multiple(
+        move |series| {
+            let out = if integer {
+                zip_broadcast(&int_values(&series[0])?, &int_values(&series[1])?)
+                    .into_iter()
+                    .map(|(l, r)| l?.checked_rem(r?))
+                    .collect::<Int64Chunked>()
+                    .into_series()
+            } else {
+                zip_broadcast(&float_values(&series[0])?, &float_values(&series[1])?)
+                    .into_iter()
+                    .map(|(l, r)| {
+                        let (l, r) = (l?, r?);
+                        if decimal && r == 0.0 {
+                            None
+                        } else {
+                            Some(l % r)
+                        }
+                    })
+                    .collect::<Float64Chunked>()
+                    .into_series()
+            };
+            Ok(Some(out.cast(&dtype)?.with_name(series[0].name())))
+        },
+        [left, right],
+        GetOutput::from_type(output),
+    )
+}
+
+//Truncates the quotient towards zero, a zero divisor, an infinite dividend and NaN operands are
+//errors
+pub fn integer_divide(left: Expr, right: Expr, integers: bool) -> Expr {
+    map_multiple(
+        move |series| {
+            let out: Int64Chunked = if integers {
+                zip_broadcast(&int_values(&series[0])?, &int_values(&series[1])?)
+                    .into_iter()
+                    .map(|(l, r)| l?.checked_div(r?))
+                    .collect()
+            } else {
+                zip_broadcast(&float_values(&series[0])?, &float_values(&series[1])?)
+                    .into_iter()
+                    .map(|(l, r)| {
+                        let quotient = (l? / r?).trunc();
+                        if quotient.is_finite() && quotient.abs() < i64::MAX as f64 {
+                            Some(quotient as i64)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            };
+            Ok(Some(out.with_name(series[0].name()).into_series()))
+        },
+        [left, right],
+        GetOutput::from_type(DataType::Int64),
+    )
+}
+
+//Halves are rounded towards positive infinity, so ROUND(-2.5) is -2
+pub fn round_half_up(expr: Expr) -> Expr {
+    when((expr.clone() - expr.clone().floor()).gt_eq(lit(0.5)))
+        .then(expr.clone().ceil())
+        .otherwise(expr.floor())
+}
+
+//Follows fn:round-half-to-even, a negative precision rounds to tens, hundreds and so on
+pub fn round_half_to_even(value: Expr, precision: Expr, value_type: NamedNodeRef) -> Expr {
+    let dtype = numeric_polars_dtype(value_type);
+    let output = dtype.clone();
+    let integer = is_integer_type(value_type);
+    map_multiple(
+        move |series| {
+            let precisions = int_values(&series[1])?;
+            let out = if integer {
+                zip_broadcast(&int_values(&series[0])?, &precisions)
+                    .into_iter()
+                    .map(|(v, p)| integer_half_to_even(v?, p?))
+                    .collect::<Int64Chunked>()
+                    .into_series()
+            } else {
+                zip_broadcast(&float_values(&series[0])?, &precisions)
+                    .into_iter()
+                    .map(|(v, p)| Some(float_half_to_even(v?, p?)))
+                    .collect::<Float64Chunked>()
+                    .into_series()
+            };
+            Ok(Some(out.cast(&dtype)?.with_name(series[0].name())))
+        },
+        [value, precision],
+        GetOutput::from_type(output),
+    )
+}
+
+fn integer_half_to_even(value: i64, precision: i64) -> Option<i64> {
+    if precision >= 0 {
+        return Some(value);
+    }
+    let magnitude = match u32::try_from(-precision)
+        .ok()
+        .and_then(|p| 10i64.checked_pow(p))
+    {
+        Some(m) => m,
+        None => return Some(0),
+    };
+    let quotient = value.div_euclid(magnitude);
+    let twice_remainder = 2 * value.rem_euclid(magnitude) as i128;
+    let up = twice_remainder > magnitude as i128
+        || (twice_remainder == magnitude as i128 && quotient % 2 != 0);
+    (quotient + i64::from(up)).checked_mul(magnitude)
+}
+
+fn float_half_to_even(value: f64, precision: i64) -> f64 {
+    //Scaling by division for negative precisions avoids the inexact powers 0.1, 0.01, ..
+    let magnitude = 10f64.powi(precision.unsigned_abs().min(400) as i32);
+    let scaled = if precision >= 0 {
+        value * magnitude
+    } else {
+        value / magnitude
+    };
+    if !scaled.is_finite() {
+        return value;
+    }
+    let floor = scaled.floor();
+    let rounded = if scaled - floor == 0.5 {
+        if floor % 2.0 == 0.0 {
+            floor
+        } else {
+            floor + 1.0
+        }
+    } else {
+        scaled.round()
+    };
+    if precision >= 0 {
+        rounded / magnitude
+    } else {
+        rounded * magnitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{INTEGER_DIVIDE, MODULUS, ROUND_TO};
+    use crate::expressions::{func_expression, literal};
+    use crate::settings::QuerySettings;
+    use oxrdf::{Literal, NamedNode, Variable};
+    use polars::prelude::{df, DataFrame, IntoLazy};
+    use representation::query_context::{Context, PathEntry};
+    use representation::solution_mapping::SolutionMappings;
+    use spargebra::algebra::Function;
+    use std::collections::HashMap;
+
+    fn context(i: u16) -> Context {
+        Context::new().extension_with(PathEntry::FunctionCall(i))
+    }
+
+    fn math(name: &str) -> String {
+        format!("{}{}", MATH_NAMESPACE, name)
+    }
+
+    //Evaluates a function of the columns in the contexts 0 and up into the context after them
+    fn evaluate(sm: SolutionMappings, iri: &str, arg_count: usize) -> (RDFNodeType, Series) {
+        let args = (0..arg_count)
+            .map(|i| Expression::Variable(Variable::new_unchecked(format!("a{}", i))))
+            .collect::<Vec<_>>();
+        let args_contexts = (0..arg_count).map(|i| (i, context(i as u16))).collect();
+        let out = context(arg_count as u16);
+        let func = Function::Custom(NamedNode::new_unchecked(iri));
+        let sm = func_expression(
+            sm,
+            &func,
+            &args,
+            args_contexts,
+            &out,
+            &QuerySettings::default(),
+        )
+        .unwrap();
+        let t = sm.rdf_node_types.get(out.as_str()).unwrap().clone();
+        let df = sm.mappings.collect().unwrap();
+        (t, df.column(out.as_str()).unwrap().clone())
+    }
+
+    //Evaluates a function of literals on a single row
+    fn call(iri: &str, args: Vec<Literal>) -> (RDFNodeType, Series) {
+        let mut sm = SolutionMappings::new(df!("row" => [0i64]).unwrap().lazy(), HashMap::new());
+        for (i, arg) in args.iter().enumerate() {
+            sm = literal(sm, arg, &context(i as u16)).unwrap();
+        }
+        evaluate(sm, iri, args.len())
+    }
+
+    fn double(iri: &str, args: Vec<Literal>) -> Option<f64> {
+        let (t, s) = call(iri, args);
+        assert_eq!(t, RDFNodeType::Literal(xsd::DOUBLE.into_owned()));
+        assert_eq!(s.dtype(), &DataType::Float64);
+        s.f64().unwrap().get(0)
+    }
+
+    #[test]
+    fn domain_errors_are_unbound() {
+        assert_eq!(double(&math("sqrt"), vec![Literal::from(-1.0)]), None);
+        assert_eq!(double(&math("log"), vec![Literal::from(-1.0)]), None);
+        assert_eq!(double(&math("sqrt"), vec![Literal::from(0.0)]), Some(0.0));
+    }
+
+    #[test]
+    fn integer_arguments_are_promoted_to_doubles() {
+        assert_eq!(double(&math("sqrt"), vec![Literal::from(4i64)]), Some(2.0));
+        let args = vec![Literal::from(2i64), Literal::from(10i64)];
+        assert_eq!(double(&math("pow"), args), Some(1024.0));
+        assert_eq!(double(&math("exp"), vec![Literal::from(0i64)]), Some(1.0));
+    }
+
+    #[test]
+    fn pi_takes_no_arguments() {
+        assert_eq!(double(&math("pi"), vec![]), Some(PI));
+    }
+
+    #[test]
+    fn ln_is_an_alias_of_log() {
+        for name in ["ln", "log"] {
+            assert_eq!(double(&math(name), vec![Literal::from(1.0)]), Some(0.0));
+            let e = double(&math(name), vec![Literal::from(E)]).unwrap();
+            assert!((e - 1.0).abs() < 1e-12);
+        }
+    }
+
+    fn integer(iri: &str, args: Vec<Literal>) -> Option<i64> {
+        let (t, s) = call(iri, args);
+        assert_eq!(t, RDFNodeType::Literal(xsd::INTEGER.into_owned()));
+        s.i64().unwrap().get(0)
+    }
+
+    fn decimal(value: &str) -> Literal {
+        Literal::new_typed_literal(value, xsd::DECIMAL)
+    }
+
+    #[test]
+    fn the_remainder_has_the_sign_of_the_dividend() {
+        let modulus = |l: i64, r: i64| integer(MODULUS, vec![Literal::from(l), Literal::from(r)]);
+        assert_eq!(modulus(-7, 2), Some(-1));
+        assert_eq!(modulus(7, -2), Some(1));
+        assert_eq!(modulus(7, 2), Some(1));
+        let args = vec![Literal::from(-7.5), Literal::from(2.0)];
+        assert_eq!(double(MODULUS, args), Some(-1.5));
+    }
+
+    #[test]
+    fn zero_divisors_are_unbound_for_integers_and_decimals_and_nan_for_doubles() {
+        let args = vec![Literal::from(7i64), Literal::from(0i64)];
+        assert_eq!(integer(MODULUS, args), None);
+        let (t, s) = call(MODULUS, vec![decimal("7.5"), decimal("0")]);
+        assert_eq!(t, RDFNodeType::Literal(xsd::DECIMAL.into_owned()));
+        assert_eq!(s.null_count(), 1);
+        let args = vec![Literal::from(7.0), Literal::from(0.0)];
+        assert!(double(MODULUS, args).unwrap().is_nan());
+    }
+
+    #[test]
+    fn floats_are_not_promoted_to_doubles() {
+        let (t, s) = call(MODULUS, vec![Literal::from(7.5f32), Literal::from(2i64)]);
+        assert_eq!(t, RDFNodeType::Literal(xsd::FLOAT.into_owned()));
+        assert_eq!(s.dtype(), &DataType::Float32);
+        assert_eq!(s.f32().unwrap().get(0), Some(1.5));
+    }
+
+    #[test]
+    fn integer_divide_truncates_towards_zero() {
+        let divide =
+            |l: i64, r: i64| integer(INTEGER_DIVIDE, vec![Literal::from(l), Literal::from(r)]);
+        assert_eq!(divide(7, 2), Some(3));
+        assert_eq!(divide(-7, 2), Some(-3));
+        assert_eq!(divide(7, -2), Some(-3));
+        let args = vec![Literal::from(-7.5), Literal::from(2.0)];
+        assert_eq!(integer(INTEGER_DIVIDE, args), Some(-3));
+        let args = vec![decimal("7.5"), Literal::from(2i64)];
+        assert_eq!(integer(INTEGER_DIVIDE, args), Some(3));
+    }
+
+    #[test]
+    fn integer_divide_by_zero_or_of_infinity_is_unbound() {
+        let cases = [
+            (Literal::from(7i64), Literal::from(0i64)),
+            (Literal::from(7.0), Literal::from(0.0)),
+            (decimal("7"), decimal("0.0")),
+            (Literal::from(f64::INFINITY), Literal::from(2.0)),
+            (Literal::from(f64::NEG_INFINITY), Literal::from(2.0)),
+            (Literal::from(f64::NAN), Literal::from(2.0)),
+        ];
+        for (l, r) in cases {
+            assert_eq!(integer(INTEGER_DIVIDE, vec![l, r]), None);
+        }
+        //A finite value divided by infinity is zero
+        let args = vec![Literal::from(7.0), Literal::from(f64::INFINITY)];
+        assert_eq!(integer(INTEGER_DIVIDE, args), Some(0));
+    }
+
+    #[test]
+    fn round_to_rounds_halves_to_even() {
+        let round_to =
+            |value: f64| double(ROUND_TO, vec![Literal::from(value), Literal::from(0i64)]);
+        assert_eq!(round_to(2.5), Some(2.0));
+        assert_eq!(round_to(3.5), Some(4.0));
+        assert_eq!(round_to(-2.5), Some(-2.0));
+        assert_eq!(round_to(2.6), Some(3.0));
+    }
+
+    #[test]
+    fn negative_precisions_round_integers_to_tens_and_hundreds() {
+        let round_to = |value: i64, precision: i64| {
+            integer(
+                ROUND_TO,
+                vec![Literal::from(value), Literal::from(precision)],
+            )
+        };
+        assert_eq!(round_to(1250, -2), Some(1200));
+        assert_eq!(round_to(1350, -2), Some(1400));
+        assert_eq!(round_to(1251, -2), Some(1300));
+        assert_eq!(round_to(-1250, -2), Some(-1200));
+        assert_eq!(round_to(15, -1), Some(20));
+        assert_eq!(round_to(1250, 2), Some(1250));
+    }
+
+    #[test]
+    fn decimal_columns_are_rounded_per_row() {
+        let c = context(0);
+        let values = Series::new(c.as_str(), [Some(0.25), Some(0.75), Some(-0.25), None]);
+        let mut sm = SolutionMappings::new(
+            DataFrame::new(vec![values]).unwrap().lazy(),
+            HashMap::from([(
+                c.as_str().to_string(),
+                RDFNodeType::Literal(xsd::DECIMAL.into_owned()),
+            )]),
+        );
+        sm = literal(sm, &Literal::from(1i64), &context(1)).unwrap();
+        let (t, s) = evaluate(sm, ROUND_TO, 2);
+        assert_eq!(t, RDFNodeType::Literal(xsd::DECIMAL.into_owned()));
+        let rounded: Vec<_> = s.f64().unwrap().into_iter().collect();
+        assert_eq!(rounded, vec![Some(0.2), Some(0.8), Some(-0.2), None]);
+    }
+}
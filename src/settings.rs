@@ -0,0 +1,57 @@
+use crate::custom_functions::{CustomFunction, CustomFunctionRegistry};
+use chrono::{DateTime, Utc};
+use oxrdf::NamedNode;
+use std::sync::Arc;
+
+#[derive(Clone, Debug)]
+pub struct QuerySettings {
+    //Relative IRIs in IRI() are resolved against the base IRI when it is set
+    pub base_iri: Option<NamedNode>,
+    //NOW() gives the same value for the whole query, the time the query starts unless it is set
+    pub now: Option<DateTime<Utc>>,
+    //RAND(), UUID() and STRUUID() are deterministic when a seed is set
+    pub seed: Option<u64>,
+    //Functions called by IRI, the chrontext functions are registered by default
+    pub functions: CustomFunctionRegistry,
+}
+
+impl Default for QuerySettings {
+    fn default() -> Self {
+        QuerySettings {
+            base_iri: None,
+            now: None,
+            seed: None,
+            functions: CustomFunctionRegistry::default(),
+        }
+    }
+}
+
+impl QuerySettings {
+    pub fn with_base_iri(mut self, base_iri: NamedNode) -> QuerySettings {
+        self.base_iri = Some(base_iri);
+        self
+    }
+
+    pub fn with_now(mut self, now: DateTime<Utc>) -> QuerySettings {
+        self.now = Some(now);
+        self
+    }
+
+    //The settings of a single query, called when the query starts so that every NOW() in it
+    //gives the same time
+    pub fn for_query(&self) -> QuerySettings {
+        let mut settings = self.clone();
+        settings.now = Some(self.now.unwrap_or_else(Utc::now));
+        settings
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> QuerySettings {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_custom_function(mut self, function: Arc<dyn CustomFunction>) -> QuerySettings {
+        self.functions.register(function);
+        self
+    }
+}
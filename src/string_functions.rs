@@ -0,0 +1,458 @@
+use crate::broadcasting::broadcast_index;
+use md5::Md5;
+use polars::datatypes::DataType;
+use polars::prelude::{
+    map_multiple, BooleanChunked, Expr, Float64Chunked, GetOutput, Int64Chunked, IntoSeries,
+    StringChunked,
+};
+use regex::{Regex, RegexBuilder};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::collections::HashMap;
+
+pub fn str_len(expr: Expr) -> Expr {
+    expr.map(
+        |s| {
+            let out: Int64Chunked = s
+                .str()?
+                .into_iter()
+                .map(|x| x.map(|x| x.chars().count() as i64))
+                .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::Int64),
+    )
+}
+
+pub fn substr(text: Expr, start: Expr, length: Option<Expr>) -> Expr {
+    let mut exprs = vec![text, start];
+    if let Some(length) = length {
+        exprs.push(length);
+    }
+    map_multiple(
+        |series| {
+            let texts = series[0].str()?;
+            let starts = series[1].cast(&DataType::Float64)?;
+            let starts = starts.f64()?;
+            let lengths = if series.len() > 2 {
+                Some(series[2].cast(&DataType::Float64)?)
+            } else {
+                None
+            };
+            let lengths: Option<&Float64Chunked> = if let Some(l) = &lengths {
+                Some(l.f64()?)
+            } else {
+                None
+            };
+            let out: StringChunked = texts
+                .into_iter()
+                .zip(starts)
+                .enumerate()
+                .map(|(i, (text, start))| {
+                    let length = if let Some(lengths) = lengths {
+                        Some(lengths.get(i)?)
+                    } else {
+                        None
+                    };
+                    Some(substring(text?, start?, length))
+                })
+                .collect();
+            Ok(Some(out.with_name(series[0].name()).into_series()))
+        },
+        exprs,
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+//Positions are 1-based codepoints, start and length are rounded as by fn:round
+fn substring(text: &str, start: f64, length: Option<f64>) -> String {
+    let start = (start + 0.5).floor();
+    let end = length.map(|l| start + (l + 0.5).floor());
+    text.chars()
+        .enumerate()
+        .filter(|(i, _)| {
+            let position = (*i + 1) as f64;
+            position >= start && end.is_none_or(|end| position < end)
+        })
+        .map(|(_, c)| c)
+        .collect()
+}
+
+pub fn str_before(text: Expr, pattern: Expr) -> Expr {
+    map_multiple(
+        |series| {
+            let texts = series[0].str()?;
+            let patterns = series[1].str()?;
+            let out: StringChunked = texts
+                .into_iter()
+                .zip(patterns)
+                .map(|(text, pattern)| {
+                    let text = text?;
+                    Some(match text.find(pattern?) {
+                        Some(i) => text[..i].to_string(),
+                        None => "".to_string(),
+                    })
+                })
+                .collect();
+            Ok(Some(out.with_name(series[0].name()).into_series()))
+        },
+        [text, pattern],
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+pub fn str_after(text: Expr, pattern: Expr) -> Expr {
+    map_multiple(
+        |series| {
+            let texts = series[0].str()?;
+            let patterns = series[1].str()?;
+            let out: StringChunked = texts
+                .into_iter()
+                .zip(patterns)
+                .map(|(text, pattern)| {
+                    let text = text?;
+                    let pattern = pattern?;
+                    Some(match text.find(pattern) {
+                        Some(i) => text[i + pattern.len()..].to_string(),
+                        None => "".to_string(),
+                    })
+                })
+                .collect();
+            Ok(Some(out.with_name(series[0].name()).into_series()))
+        },
+        [text, pattern],
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+pub fn encode_for_uri(expr: Expr) -> Expr {
+    expr.map(
+        |s| {
+            let out: StringChunked = s
+                .str()?
+                .into_iter()
+                .map(|x| x.map(percent_encode))
+                .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+pub fn regex_with_flags(pattern: &str, flags: &str) -> Result<Regex, String> {
+    let mut builder = RegexBuilder::new(pattern);
+    for flag in flags.chars() {
+        match flag {
+            'i' => builder.case_insensitive(true),
+            's' => builder.dot_matches_new_line(true),
+            'm' => builder.multi_line(true),
+            'x' => builder.ignore_whitespace(true),
+            _ => return Err(format!("unsupported flag {}", flag)),
+        };
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+//Rewrites an XPath replacement string with $N group references to the syntax of the regex crate.
+//As in fn:replace, digits after the first are part of N only while N is at most the number of
+//groups, and a $ without a digit or a \ that does not escape $ or \ makes the replacement invalid.
+pub fn xpath_replacement(replacement: &str, groups: usize) -> Option<String> {
+    let mut rewritten = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                '$' => rewritten.push_str("$$"),
+                '\\' => rewritten.push('\\'),
+                _ => return None,
+            },
+            '$' => {
+                let mut group = chars.next()?.to_digit(10)? as usize;
+                while let Some(d) = chars.peek().and_then(|d| d.to_digit(10)) {
+                    if group * 10 + d as usize > groups {
+                        break;
+                    }
+                    group = group * 10 + d as usize;
+                    chars.next();
+                }
+                rewritten.push_str(&format!("${{{}}}", group));
+            }
+            _ => rewritten.push(c),
+        }
+    }
+    Some(rewritten)
+}
+
+fn str_at(ca: &StringChunked, i: usize) -> Option<&str> {
+    ca.get(broadcast_index(ca.len(), i))
+}
+
+fn cached_regex<'a>(
+    cache: &'a mut HashMap<(String, String), Option<Regex>>,
+    pattern: &str,
+    flags: &str,
+) -> Option<&'a Regex> {
+    cache
+        .entry((pattern.to_string(), flags.to_string()))
+        .or_insert_with(|| regex_with_flags(pattern, flags).ok())
+        .as_ref()
+}
+
+pub fn regex_matches(text: Expr, pattern: Expr, flags: Expr) -> Expr {
+    map_multiple(
+        |series| {
+            let texts = series[0].str()?;
+            let patterns = series[1].str()?;
+            let flags = series[2].str()?;
+            let len = series.iter().map(|s| s.len()).max().unwrap_or(0);
+            let mut cache = HashMap::new();
+            let out: BooleanChunked = (0..len)
+                .map(|i| {
+                    let regex = cached_regex(&mut cache, str_at(patterns, i)?, str_at(flags, i)?)?;
+                    Some(regex.is_match(str_at(texts, i)?))
+                })
+                .collect();
+            Ok(Some(out.with_name(series[0].name()).into_series()))
+        },
+        [text, pattern, flags],
+        GetOutput::from_type(DataType::Boolean),
+    )
+}
+
+pub fn regex_replace(text: Expr, pattern: Expr, replacement: Expr, flags: Expr) -> Expr {
+    map_multiple(
+        |series| {
+            let texts = series[0].str()?;
+            let patterns = series[1].str()?;
+            let replacements = series[2].str()?;
+            let flags = series[3].str()?;
+            let len = series.iter().map(|s| s.len()).max().unwrap_or(0);
+            let mut cache = HashMap::new();
+            let out: StringChunked = (0..len)
+                .map(|i| {
+                    let regex = cached_regex(&mut cache, str_at(patterns, i)?, str_at(flags, i)?)?;
+                    let groups = regex.captures_len() - 1;
+                    let replacement = xpath_replacement(str_at(replacements, i)?, groups)?;
+                    Some(
+                        regex
+                            .replace_all(str_at(texts, i)?, replacement.as_str())
+                            .into_owned(),
+                    )
+                })
+                .collect();
+            Ok(Some(out.with_name(series[0].name()).into_series()))
+        },
+        [text, pattern, replacement, flags],
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+pub fn md5(expr: Expr) -> Expr {
+    hex_digest::<Md5>(expr)
+}
+
+pub fn sha1(expr: Expr) -> Expr {
+    hex_digest::<Sha1>(expr)
+}
+
+pub fn sha256(expr: Expr) -> Expr {
+    hex_digest::<Sha256>(expr)
+}
+
+pub fn sha384(expr: Expr) -> Expr {
+    hex_digest::<Sha384>(expr)
+}
+
+pub fn sha512(expr: Expr) -> Expr {
+    hex_digest::<Sha512>(expr)
+}
+
+fn hex_digest<D: Digest + 'static>(expr: Expr) -> Expr {
+    expr.map(
+        |s| {
+            let out: StringChunked = s
+                .str()?
+                .into_iter()
+                .map(|x| x.map(|x| format!("{:x}", D::digest(x.as_bytes()))))
+                .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::{col, df, lit, DataFrame, IntoLazy};
+
+    fn first_string(df: DataFrame, expr: Expr) -> Option<String> {
+        let out = df.lazy().select([expr.alias("out")]).collect().unwrap();
+        let value = out.column("out").unwrap().str().unwrap().get(0);
+        value.map(|v| v.to_string())
+    }
+
+    fn substr_of(text: &str, start: f64, length: Option<f64>) -> Option<String> {
+        let df = df!("s" => [text]).unwrap();
+        first_string(df, substr(col("s"), lit(start), length.map(lit)))
+    }
+
+    #[test]
+    fn substr_counts_code_points() {
+        assert_eq!(substr_of("😀abc", 2.0, Some(2.0)), Some("ab".to_string()));
+        assert_eq!(substr_of("😀abc", 1.0, Some(1.0)), Some("😀".to_string()));
+        assert_eq!(substr_of("😀abc", 3.0, None), Some("bc".to_string()));
+    }
+
+    #[test]
+    fn substr_rounds_start_and_length() {
+        assert_eq!(substr_of("12345", 1.5, Some(2.6)), Some("234".to_string()));
+        assert_eq!(substr_of("12345", 0.0, Some(3.0)), Some("12".to_string()));
+        assert_eq!(substr_of("12345", -3.0, Some(5.0)), Some("1".to_string()));
+    }
+
+    #[test]
+    fn encode_for_uri_percent_encodes_utf8_bytes() {
+        let df = df!("s" => ["Los Angeles", "~bébé"]).unwrap();
+        let out = df
+            .lazy()
+            .select([encode_for_uri(col("s"))])
+            .collect()
+            .unwrap();
+        let values: Vec<_> = out
+            .column("s")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(values, vec![Some("Los%20Angeles"), Some("~b%C3%A9b%C3%A9")]);
+    }
+
+    fn digest_of_abc(digest: fn(Expr) -> Expr) -> String {
+        let df = df!("s" => ["abc"]).unwrap();
+        let out = df.lazy().select([digest(col("s"))]).collect().unwrap();
+        let value = out.column("s").unwrap().str().unwrap().get(0).unwrap();
+        value.to_string()
+    }
+
+    fn matches(text: &str, pattern: &str, flags: &str) -> Option<bool> {
+        let df = df!("t" => [text], "p" => [pattern], "f" => [flags]).unwrap();
+        let out = df
+            .lazy()
+            .select([regex_matches(col("t"), col("p"), col("f")).alias("out")])
+            .collect()
+            .unwrap();
+        out.column("out").unwrap().bool().unwrap().get(0)
+    }
+
+    fn replaced(text: &str, pattern: &str, replacement: &str) -> Option<String> {
+        let df = df!("t" => [text], "p" => [pattern], "r" => [replacement]).unwrap();
+        first_string(df, regex_replace(col("t"), col("p"), col("r"), lit("")))
+    }
+
+    #[test]
+    fn regex_flags() {
+        assert_eq!(matches("ABC", "abc", ""), Some(false));
+        assert_eq!(matches("ABC", "abc", "i"), Some(true));
+        assert_eq!(matches("a\nb", "a.b", ""), Some(false));
+        assert_eq!(matches("a\nb", "a.b", "s"), Some(true));
+        assert_eq!(matches("a\nb", "^b$", ""), Some(false));
+        assert_eq!(matches("a\nb", "^b$", "m"), Some(true));
+        assert_eq!(matches("abc", "a b c", ""), Some(false));
+        assert_eq!(matches("abc", "a b c", "x"), Some(true));
+        assert_eq!(matches("abc", "abc", "q"), None);
+    }
+
+    #[test]
+    fn regex_patterns_per_row() {
+        let df = df!(
+            "t" => ["abc", "xyz", "abc"],
+            "p" => ["^a", "z$", "("]
+        )
+        .unwrap();
+        let out = df
+            .lazy()
+            .select([regex_matches(col("t"), col("p"), lit("")).alias("out")])
+            .collect()
+            .unwrap();
+        let values: Vec<_> = out
+            .column("out")
+            .unwrap()
+            .bool()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(values, vec![Some(true), Some(true), None]);
+    }
+
+    #[test]
+    fn replace_with_group_references() {
+        assert_eq!(replaced("abcd", "(b)(c)", "$2$1"), Some("acbd".to_string()));
+        //Digits that would give a group number above the number of groups are kept as text
+        assert_eq!(replaced("abc", "(b)", "$12"), Some("ab2c".to_string()));
+        assert_eq!(replaced("abc", "(b)", "$2"), Some("ac".to_string()));
+        assert_eq!(replaced("abc", "b", "\\$\\\\"), Some("a$\\c".to_string()));
+    }
+
+    #[test]
+    fn invalid_replacements_are_unbound() {
+        assert_eq!(xpath_replacement("\\", 0), None);
+        assert_eq!(xpath_replacement("\\x", 0), None);
+        assert_eq!(xpath_replacement("$", 0), None);
+        assert_eq!(xpath_replacement("$x", 0), None);
+        assert_eq!(replaced("abc", "b", "\\n"), None);
+        assert_eq!(replaced("abc", "b", "x$"), None);
+    }
+
+    #[test]
+    fn md5_of_abc() {
+        assert_eq!(digest_of_abc(md5), "900150983cd24fb0d6963f7d28e17f72");
+    }
+
+    #[test]
+    fn sha1_of_abc() {
+        assert_eq!(
+            digest_of_abc(sha1),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+    }
+
+    #[test]
+    fn sha256_of_abc() {
+        assert_eq!(
+            digest_of_abc(sha256),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn sha384_of_abc() {
+        assert_eq!(
+            digest_of_abc(sha384),
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed\
+             8086072ba1e7cc2358baeca134c825a7"
+        );
+    }
+
+    #[test]
+    fn sha512_of_abc() {
+        assert_eq!(
+            digest_of_abc(sha512),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+    }
+}
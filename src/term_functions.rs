@@ -0,0 +1,140 @@
+use oxiri::Iri;
+use polars::datatypes::DataType;
+use polars::prelude::{
+    Expr, Float64Chunked, GetOutput, IntoSeries, PolarsResult, Series, StringChunked,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use uuid::{Builder, Uuid};
+
+pub fn resolve_iri(expr: Expr, base_iri: Option<String>) -> Expr {
+    expr.map(
+        move |s| {
+            let base = base_iri.as_ref().and_then(|b| Iri::parse(b.as_str()).ok());
+            let out: StringChunked = s
+                .str()?
+                .into_iter()
+                .map(|x| {
+                    let iri = if let Some(base) = &base {
+                        base.resolve(x?).ok()?
+                    } else {
+                        Iri::parse(x?.to_string()).ok()?
+                    };
+                    Some(iri.into_inner())
+                })
+                .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+//Blank node labels may only contain a restricted set of characters
+pub fn hex_encode(expr: Expr) -> Expr {
+    expr.map(
+        |s| {
+            let out: StringChunked = s
+                .str()?
+                .into_iter()
+                .map(|x| x.map(|x| x.bytes().map(|b| format!("{:02x}", b)).collect::<String>()))
+                .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+//FNV-1a, which unlike DefaultHasher gives the same hash in every Rust release
+fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+//The finalizer of splitmix64, so that neighbouring rows get unrelated seeds
+fn mix(x: u64) -> u64 {
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+//Each call site gets its own generator, so that two calls to RAND() in a query differ. ChaCha8Rng
+//is used rather than StdRng, whose algorithm may change, so that a seed gives the same values in
+//every release
+fn rng(seed: Option<u64>, call_site: &str) -> ChaCha8Rng {
+    if let Some(seed) = seed {
+        ChaCha8Rng::seed_from_u64(mix(seed ^ stable_hash(call_site.as_bytes())))
+    } else {
+        ChaCha8Rng::from_entropy()
+    }
+}
+
+//With a seed, each row gets a generator seeded by its row index, so that values are the same
+//however Polars splits the rows into batches
+fn row_values<T>(
+    index: &Series,
+    seed: Option<u64>,
+    call_site: &str,
+    value: impl Fn(&mut ChaCha8Rng) -> T,
+) -> PolarsResult<Vec<T>> {
+    if let Some(seed) = seed {
+        let call_site_seed = mix(seed ^ stable_hash(call_site.as_bytes()));
+        let rows = index.cast(&DataType::UInt64)?;
+        Ok(rows
+            .u64()?
+            .into_iter()
+            .map(|row| {
+                let row_seed = mix(call_site_seed ^ mix(row.unwrap_or_default()));
+                value(&mut ChaCha8Rng::seed_from_u64(row_seed))
+            })
+            .collect())
+    } else {
+        let mut rng = rng(None, call_site);
+        Ok((0..index.len()).map(|_| value(&mut rng)).collect())
+    }
+}
+
+pub fn random_doubles(index: Expr, seed: Option<u64>, call_site: String) -> Expr {
+    index.map(
+        move |s| {
+            let out: Float64Chunked = row_values(&s, seed, &call_site, |rng| rng.gen::<f64>())?
+                .into_iter()
+                .map(Some)
+                .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::Float64),
+    )
+}
+
+pub fn random_uuids(index: Expr, seed: Option<u64>, call_site: String, prefix: &str) -> Expr {
+    let prefix = prefix.to_string();
+    index.map(
+        move |s| {
+            let out: StringChunked = row_values(&s, seed, &call_site, |rng| {
+                let uuid = Builder::from_random_bytes(rng.gen()).into_uuid();
+                Some(format!("{}{}", prefix, uuid))
+            })?
+            .into_iter()
+            .collect();
+            Ok(Some(out.with_name(s.name()).into_series()))
+        },
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+//Identifies a call site, reproducibly when there is a seed
+pub fn call_site_uuid(seed: Option<u64>, call_site: &str) -> Uuid {
+    Builder::from_random_bytes(rng(seed, call_site).gen()).into_uuid()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_hash_is_fnv_1a() {
+        assert_eq!(stable_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(stable_hash(b"a"), 0xaf63dc4c8601ec8c);
+    }
+}
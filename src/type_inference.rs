@@ -0,0 +1,995 @@
+use crate::casts::is_xsd_cast;
+use crate::custom_functions::CustomFunctionRegistry;
+use crate::datetime_functions::duration_subtype;
+use crate::errors::QueryProcessingError;
+use crate::graph_patterns::union_rdf_node_types;
+use crate::type_promotion::{
+    numeric_rank, promote_all_numeric_types, promote_numeric_types, temporal_operator_type,
+};
+use oxrdf::vocab::{rdf, xsd};
+use oxrdf::NamedNode;
+use polars::prelude::Operator;
+use representation::query_context::{Context, PathEntry};
+use representation::{
+    literal_is_boolean, literal_is_datetime, literal_is_numeric, literal_is_string,
+    BaseRDFNodeType, RDFNodeType,
+};
+use spargebra::algebra::{Expression, Function};
+use std::collections::HashMap;
+
+//The types of the contexts of an expression. Type errors give unbound values when the expression
+//is evaluated, so they are collected here instead of failing the query.
+#[derive(Debug, Default)]
+pub struct InferredTypes {
+    pub types: HashMap<String, RDFNodeType>,
+    pub type_errors: Vec<QueryProcessingError>,
+}
+
+//FILTER, BIND and ORDER BY in graph_patterns run this over their expressions. Arity errors,
+//unknown variables and unsupported functions are errors, type errors are collected.
+pub fn infer_expression_types(
+    expression: &Expression,
+    rdf_node_types: &HashMap<String, RDFNodeType>,
+    functions: &CustomFunctionRegistry,
+    context: &Context,
+) -> Result<InferredTypes, QueryProcessingError> {
+    let mut inferred = InferredTypes::default();
+    infer_expression_type(
+        expression,
+        rdf_node_types,
+        functions,
+        context,
+        &mut inferred,
+    )?;
+    Ok(inferred)
+}
+
+pub fn infer_expression_type(
+    expression: &Expression,
+    rdf_node_types: &HashMap<String, RDFNodeType>,
+    functions: &CustomFunctionRegistry,
+    context: &Context,
+    inferred: &mut InferredTypes,
+) -> Result<RDFNodeType, QueryProcessingError> {
+    let t = match expression {
+        Expression::NamedNode(..) => RDFNodeType::IRI,
+        Expression::Literal(l) => {
+            //Durations are typed as in expressions::literal
+            let t = RDFNodeType::Literal(l.datatype().into_owned());
+            if let Some((subtype, _)) = duration_subtype(l.datatype(), l.value()) {
+                RDFNodeType::Literal(subtype.into_owned())
+            } else if is_duration_type(&t) {
+                RDFNodeType::None
+            } else {
+                t
+            }
+        }
+        Expression::Variable(v) => {
+            if let Some(t) = rdf_node_types.get(v.as_str()) {
+                t.clone()
+            } else {
+                return Err(QueryProcessingError::VariableNotFound(
+                    v.as_str().to_string(),
+                    context.as_str().to_string(),
+                ));
+            }
+        }
+        Expression::Or(left, right) => infer_binary_type(
+            expression,
+            Operator::Or,
+            left,
+            right,
+            PathEntry::OrLeft,
+            PathEntry::OrRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::And(left, right) => infer_binary_type(
+            expression,
+            Operator::And,
+            left,
+            right,
+            PathEntry::AndLeft,
+            PathEntry::AndRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::Equal(left, right) => infer_binary_type(
+            expression,
+            Operator::Eq,
+            left,
+            right,
+            PathEntry::EqualLeft,
+            PathEntry::EqualRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::Greater(left, right) => infer_binary_type(
+            expression,
+            Operator::Gt,
+            left,
+            right,
+            PathEntry::GreaterLeft,
+            PathEntry::GreaterRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::GreaterOrEqual(left, right) => infer_binary_type(
+            expression,
+            Operator::GtEq,
+            left,
+            right,
+            PathEntry::GreaterOrEqualLeft,
+            PathEntry::GreaterOrEqualRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::Less(left, right) => infer_binary_type(
+            expression,
+            Operator::Lt,
+            left,
+            right,
+            PathEntry::LessLeft,
+            PathEntry::LessRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::LessOrEqual(left, right) => infer_binary_type(
+            expression,
+            Operator::LtEq,
+            left,
+            right,
+            PathEntry::LessOrEqualLeft,
+            PathEntry::LessOrEqualRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::Add(left, right) => infer_binary_type(
+            expression,
+            Operator::Plus,
+            left,
+            right,
+            PathEntry::AddLeft,
+            PathEntry::AddRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::Subtract(left, right) => infer_binary_type(
+            expression,
+            Operator::Minus,
+            left,
+            right,
+            PathEntry::SubtractLeft,
+            PathEntry::SubtractRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::Multiply(left, right) => infer_binary_type(
+            expression,
+            Operator::Multiply,
+            left,
+            right,
+            PathEntry::MultiplyLeft,
+            PathEntry::MultiplyRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::Divide(left, right) => infer_binary_type(
+            expression,
+            Operator::Divide,
+            left,
+            right,
+            PathEntry::DivideLeft,
+            PathEntry::DivideRight,
+            rdf_node_types,
+            functions,
+            context,
+            inferred,
+        )?,
+        Expression::SameTerm(left, right) => {
+            infer_expression_type(
+                left,
+                rdf_node_types,
+                functions,
+                &context.extension_with(PathEntry::SameTermLeft),
+                inferred,
+            )?;
+            infer_expression_type(
+                right,
+                rdf_node_types,
+                functions,
+                &context.extension_with(PathEntry::SameTermRight),
+                inferred,
+            )?;
+            RDFNodeType::Literal(xsd::BOOLEAN.into_owned())
+        }
+        Expression::In(left, rights) => {
+            infer_expression_type(
+                left,
+                rdf_node_types,
+                functions,
+                &context.extension_with(PathEntry::InLeft),
+                inferred,
+            )?;
+            for (i, right) in rights.iter().enumerate() {
+                infer_expression_type(
+                    right,
+                    rdf_node_types,
+                    functions,
+                    &context.extension_with(PathEntry::InRight(i as u16)),
+                    inferred,
+                )?;
+            }
+            RDFNodeType::Literal(xsd::BOOLEAN.into_owned())
+        }
+        Expression::UnaryPlus(inner) | Expression::UnaryMinus(inner) => {
+            let entry = if matches!(expression, Expression::UnaryPlus(..)) {
+                PathEntry::UnaryPlus
+            } else {
+                PathEntry::UnaryMinus
+            };
+            let inner_type = infer_expression_type(
+                inner,
+                rdf_node_types,
+                functions,
+                &context.extension_with(entry),
+                inferred,
+            )?;
+            let minus = matches!(expression, Expression::UnaryMinus(..));
+            if let Some(t) = unary_operator_type(&inner_type, minus) {
+                t
+            } else {
+                type_error(inferred, context, expression, vec![inner_type])
+            }
+        }
+        Expression::Not(inner) => {
+            infer_expression_type(
+                inner,
+                rdf_node_types,
+                functions,
+                &context.extension_with(PathEntry::Not),
+                inferred,
+            )?;
+            RDFNodeType::Literal(xsd::BOOLEAN.into_owned())
+        }
+        Expression::Exists(..) | Expression::Bound(..) => {
+            RDFNodeType::Literal(xsd::BOOLEAN.into_owned())
+        }
+        Expression::If(left, middle, right) => {
+            infer_expression_type(
+                left,
+                rdf_node_types,
+                functions,
+                &context.extension_with(PathEntry::IfLeft),
+                inferred,
+            )?;
+            let middle_type = infer_expression_type(
+                middle,
+                rdf_node_types,
+                functions,
+                &context.extension_with(PathEntry::IfMiddle),
+                inferred,
+            )?;
+            let right_type = infer_expression_type(
+                right,
+                rdf_node_types,
+                functions,
+                &context.extension_with(PathEntry::IfRight),
+                inferred,
+            )?;
+            unified_type(&[middle_type, right_type])
+        }
+        Expression::Coalesce(inner) => {
+            let mut inner_types = vec![];
+            for (i, e) in inner.iter().enumerate() {
+                inner_types.push(infer_expression_type(
+                    e,
+                    rdf_node_types,
+                    functions,
+                    &context.extension_with(PathEntry::Coalesce(i as u16)),
+                    inferred,
+                )?);
+            }
+            unified_type(&inner_types)
+        }
+        Expression::FunctionCall(func, args) => {
+            check_function_arity(func, args.len(), functions)?;
+            let mut arg_types = vec![];
+            for (i, e) in args.iter().enumerate() {
+                arg_types.push(infer_expression_type(
+                    e,
+                    rdf_node_types,
+                    functions,
+                    &context.extension_with(PathEntry::FunctionCall(i as u16)),
+                    inferred,
+                )?);
+            }
+            if splits_multitype_arguments(func)
+                && arg_types
+                    .iter()
+                    .any(|t| matches!(t, RDFNodeType::MultiType(..)))
+            {
+                //Combinations of base types that are type errors give unbound results
+                let options: Vec<_> = arg_types.iter().map(base_types).collect();
+                let mut types = vec![];
+                for combination in combinations(&options) {
+                    if argument_types_valid(func, &combination) {
+                        types.push(function_type(func, args, &combination, functions)?);
+                    }
+                }
+                if types.is_empty() {
+                    type_error(inferred, context, expression, arg_types)
+                } else {
+                    unified_type(&types)
+                }
+            } else if argument_types_valid(func, &arg_types) {
+                function_type(func, args, &arg_types, functions)?
+            } else {
+                type_error(inferred, context, expression, arg_types)
+            }
+        }
+    };
+    inferred
+        .types
+        .insert(context.as_str().to_string(), t.clone());
+    Ok(t)
+}
+
+//Records a type error in an expression, which is unbound when evaluated. Unbound operands are not
+//type errors, and errors in the expressions that give them are already recorded.
+fn type_error(
+    inferred: &mut InferredTypes,
+    context: &Context,
+    expression: &Expression,
+    types: Vec<RDFNodeType>,
+) -> RDFNodeType {
+    if !types.contains(&RDFNodeType::None) {
+        inferred
+            .type_errors
+            .push(QueryProcessingError::IncompatibleTypes(
+                context.as_str().to_string(),
+                expression.to_string(),
+                types,
+            ));
+    }
+    RDFNodeType::None
+}
+
+pub fn check_function_arity(
+    func: &Function,
+    arg_count: usize,
+    functions: &CustomFunctionRegistry,
+) -> Result<(), QueryProcessingError> {
+    let (min, max) = match func {
+        Function::Uuid | Function::StrUuid | Function::Rand | Function::Now => (0, Some(0)),
+        Function::BNode => (0, Some(1)),
+        Function::Concat => (0, None),
+        Function::Contains
+        | Function::StrStarts
+        | Function::StrEnds
+        | Function::StrBefore
+        | Function::StrAfter
+        | Function::LangMatches
+        | Function::StrLang
+        | Function::StrDt => (2, Some(2)),
+        Function::Regex | Function::SubStr => (2, Some(3)),
+        Function::Replace => (3, Some(4)),
+        Function::Custom(nn) => {
+            if is_xsd_cast(nn.as_ref()) {
+                (1, Some(1))
+            } else if let Some(custom) = functions.get(nn.as_str()) {
+                (
+                    custom.arity(),
+                    Some(custom.arity() + custom.optional_arguments()),
+                )
+            } else {
+                return Err(QueryProcessingError::UnsupportedFunction(nn.to_string()));
+            }
+        }
+        Function::Year
+        | Function::Month
+        | Function::Day
+        | Function::Hours
+        | Function::Minutes
+        | Function::Seconds
+        | Function::Timezone
+        | Function::Tz
+        | Function::Abs
+        | Function::Ceil
+        | Function::Floor
+        | Function::Round
+        | Function::StrLen
+        | Function::UCase
+        | Function::LCase
+        | Function::EncodeForUri
+        | Function::Md5
+        | Function::Sha1
+        | Function::Sha256
+        | Function::Sha384
+        | Function::Sha512
+        | Function::Iri
+        | Function::Lang
+        | Function::IsIri
+        | Function::IsBlank
+        | Function::IsLiteral
+        | Function::IsNumeric
+        | Function::Datatype => (1, Some(1)),
+        //Unsupported functions are reported when their type is inferred
+        _ => return Ok(()),
+    };
+    if arg_count < min || max.is_some_and(|max| arg_count > max) {
+        let expected = match max {
+            None => format!("at least {}", min),
+            Some(max) if max == min => min.to_string(),
+            Some(max) if max == min + 1 => format!("{} or {}", min, max),
+            Some(max) => format!("{} to {}", min, max),
+        };
+        return Err(QueryProcessingError::WrongNumberOfArguments(
+            func.to_string(),
+            expected,
+            arg_count,
+        ));
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn infer_binary_type(
+    expression: &Expression,
+    op: Operator,
+    left: &Expression,
+    right: &Expression,
+    left_entry: PathEntry,
+    right_entry: PathEntry,
+    rdf_node_types: &HashMap<String, RDFNodeType>,
+    functions: &CustomFunctionRegistry,
+    context: &Context,
+    inferred: &mut InferredTypes,
+) -> Result<RDFNodeType, QueryProcessingError> {
+    let left_type = infer_expression_type(
+        left,
+        rdf_node_types,
+        functions,
+        &context.extension_with(left_entry),
+        inferred,
+    )?;
+    let right_type = infer_expression_type(
+        right,
+        rdf_node_types,
+        functions,
+        &context.extension_with(right_entry),
+        inferred,
+    )?;
+    if let Some(t) = binary_operator_type(op, &left_type, &right_type) {
+        Ok(t)
+    } else {
+        Ok(type_error(
+            inferred,
+            context,
+            expression,
+            vec![left_type, right_type],
+        ))
+    }
+}
+
+//The output type of a binary operator, or None if no combination of base types is compatible
+pub fn binary_operator_type(
+    op: Operator,
+    left_type: &RDFNodeType,
+    right_type: &RDFNodeType,
+) -> Option<RDFNodeType> {
+    let mut results: Vec<NamedNode> = vec![];
+    let mut compatible = false;
+    for l in base_types(left_type) {
+        for r in base_types(right_type) {
+            match op {
+                Operator::And | Operator::Or => {
+                    compatible = true;
+                }
+                Operator::LtEq | Operator::GtEq | Operator::Gt | Operator::Lt | Operator::Eq => {
+                    if op == Operator::Eq || comparable_types(op, &l, &r) {
+                        compatible = true;
+                    }
+                }
+                _ => {
+                    if let (RDFNodeType::Literal(l1), RDFNodeType::Literal(l2)) = (&l, &r) {
+                        if let Some(t) =
+                            promote_numeric_types(l1.as_ref(), l2.as_ref(), op == Operator::Divide)
+                                .or_else(|| temporal_operator_type(op, l1.as_ref(), l2.as_ref()))
+                        {
+                            results.push(t);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    match op {
+        Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide => {
+            arithmetic_result_type(&results)
+        }
+        _ if compatible => Some(RDFNodeType::Literal(xsd::BOOLEAN.into_owned())),
+        _ => None,
+    }
+}
+
+//Numeric results of arithmetic on the base types of multi typed values are promoted to a common
+//type while temporal results keep their own types, so that mixed results give a multi type
+pub fn arithmetic_result_type(types: &[NamedNode]) -> Option<RDFNodeType> {
+    let promoted = promote_all_numeric_types(types.iter().map(|t| t.as_ref()));
+    let mut base_types = vec![];
+    for t in types {
+        let t = match &promoted {
+            Some(promoted) if numeric_rank(t.as_ref()).is_some() => promoted.clone(),
+            _ => t.clone(),
+        };
+        let bt = BaseRDFNodeType::Literal(t);
+        if !base_types.contains(&bt) {
+            base_types.push(bt);
+        }
+    }
+    match base_types.len() {
+        0 => None,
+        1 => Some(base_types.remove(0).as_rdf_node_type()),
+        _ => {
+            base_types.sort();
+            Some(RDFNodeType::MultiType(base_types))
+        }
+    }
+}
+
+//Unary minus keeps signed numeric subtypes, other integer subtypes become xsd:integer
+pub fn unary_operator_type(t: &RDFNodeType, minus: bool) -> Option<RDFNodeType> {
+    if let RDFNodeType::Literal(l) = t {
+        if is_duration_type(t) {
+            Some(t.clone())
+        } else if numeric_rank(l.as_ref()).is_some() {
+            if !minus
+                || matches!(
+                    l.as_ref(),
+                    xsd::INTEGER
+                        | xsd::LONG
+                        | xsd::INT
+                        | xsd::SHORT
+                        | xsd::BYTE
+                        | xsd::DECIMAL
+                        | xsd::FLOAT
+                        | xsd::DOUBLE
+                )
+            {
+                Some(t.clone())
+            } else {
+                Some(RDFNodeType::Literal(xsd::INTEGER.into_owned()))
+            }
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+pub fn function_type(
+    func: &Function,
+    args: &[Expression],
+    arg_types: &[RDFNodeType],
+    functions: &CustomFunctionRegistry,
+) -> Result<RDFNodeType, QueryProcessingError> {
+    let t = match func {
+        Function::Year | Function::Month | Function::Day | Function::Hours | Function::Minutes => {
+            RDFNodeType::Literal(xsd::INTEGER.into_owned())
+        }
+        Function::Seconds => RDFNodeType::Literal(xsd::DECIMAL.into_owned()),
+        Function::Abs
+        | Function::Round
+        | Function::UCase
+        | Function::LCase
+        | Function::SubStr
+        | Function::Replace => first_type(func, arg_types)?,
+        Function::StrLen => RDFNodeType::Literal(xsd::INTEGER.into_owned()),
+        Function::EncodeForUri
+        | Function::Md5
+        | Function::Sha1
+        | Function::Sha256
+        | Function::Sha384
+        | Function::Sha512 => RDFNodeType::Literal(xsd::STRING.into_owned()),
+        Function::StrBefore | Function::StrAfter => {
+            if is_lang_string_type(&first_type(func, arg_types)?) {
+                lang_string_or_simple_literal_type()
+            } else {
+                RDFNodeType::Literal(xsd::STRING.into_owned())
+            }
+        }
+        Function::Ceil | Function::Floor => RDFNodeType::Literal(xsd::INTEGER.into_owned()),
+        Function::Timezone => RDFNodeType::Literal(xsd::DAY_TIME_DURATION.into_owned()),
+        Function::Tz => RDFNodeType::Literal(xsd::STRING.into_owned()),
+        Function::Concat => {
+            if !arg_types.is_empty() && arg_types.iter().all(is_lang_string_type) {
+                lang_string_or_simple_literal_type()
+            } else {
+                RDFNodeType::Literal(xsd::STRING.into_owned())
+            }
+        }
+        Function::Regex
+        | Function::Contains
+        | Function::StrStarts
+        | Function::StrEnds
+        | Function::LangMatches => RDFNodeType::Literal(xsd::BOOLEAN.into_owned()),
+        Function::IsIri | Function::IsBlank | Function::IsLiteral | Function::IsNumeric => {
+            RDFNodeType::Literal(xsd::BOOLEAN.into_owned())
+        }
+        Function::Datatype | Function::Iri => RDFNodeType::IRI,
+        Function::BNode => RDFNodeType::BlankNode,
+        Function::Uuid => RDFNodeType::IRI,
+        Function::StrUuid => RDFNodeType::Literal(xsd::STRING.into_owned()),
+        Function::Rand => RDFNodeType::Literal(xsd::DOUBLE.into_owned()),
+        Function::Now => RDFNodeType::Literal(xsd::DATE_TIME.into_owned()),
+        //STRDT is unbound unless its datatype is a constant
+        Function::StrDt => match args.get(1) {
+            Some(Expression::NamedNode(nn)) if nn.as_ref() != rdf::LANG_STRING => {
+                RDFNodeType::Literal(nn.clone())
+            }
+            _ => RDFNodeType::None,
+        },
+        Function::Lang => RDFNodeType::Literal(xsd::STRING.into_owned()),
+        Function::StrLang => RDFNodeType::Literal(rdf::LANG_STRING.into_owned()),
+        Function::Custom(nn) => {
+            let iri = nn.as_str();
+            if is_xsd_cast(nn.as_ref()) {
+                RDFNodeType::Literal(nn.clone())
+            } else if let Some(custom) = functions.get(iri) {
+                custom.output_type(arg_types).unwrap_or(RDFNodeType::None)
+            } else {
+                return Err(QueryProcessingError::UnsupportedFunction(nn.to_string()));
+            }
+        }
+        _ => return Err(QueryProcessingError::UnsupportedFunction(func.to_string())),
+    };
+    Ok(t)
+}
+
+fn lang_string_or_simple_literal_type() -> RDFNodeType {
+    let mut types = vec![
+        BaseRDFNodeType::Literal(rdf::LANG_STRING.into_owned()),
+        BaseRDFNodeType::Literal(xsd::STRING.into_owned()),
+    ];
+    types.sort();
+    RDFNodeType::MultiType(types)
+}
+
+fn first_type(
+    func: &Function,
+    arg_types: &[RDFNodeType],
+) -> Result<RDFNodeType, QueryProcessingError> {
+    arg_types
+        .first()
+        .cloned()
+        .ok_or_else(|| QueryProcessingError::MissingArgumentContext(func.to_string(), 0))
+}
+
+pub fn unified_type(types: &[RDFNodeType]) -> RDFNodeType {
+    let mut unified: Option<RDFNodeType> = None;
+    for t in types {
+        if t != &RDFNodeType::None {
+            unified = Some(if let Some(u) = unified {
+                union_rdf_node_types(&u, t)
+            } else {
+                t.clone()
+            });
+        }
+    }
+    unified.unwrap_or(RDFNodeType::None)
+}
+
+fn base_types(t: &RDFNodeType) -> Vec<RDFNodeType> {
+    if let RDFNodeType::MultiType(types) = t {
+        types.iter().map(|bt| bt.as_rdf_node_type()).collect()
+    } else {
+        vec![t.clone()]
+    }
+}
+
+//Term tests, LANG and casts handle multi typed arguments themselves, other functions are
+//evaluated for each combination of the base types of their arguments
+pub fn splits_multitype_arguments(func: &Function) -> bool {
+    match func {
+        Function::IsIri
+        | Function::IsBlank
+        | Function::IsLiteral
+        | Function::IsNumeric
+        | Function::Datatype
+        | Function::Lang => false,
+        Function::Custom(nn) => !is_xsd_cast(nn.as_ref()),
+        _ => true,
+    }
+}
+
+//Every way of picking one of the options for each position
+pub fn combinations<T: Clone>(options: &[Vec<T>]) -> Vec<Vec<T>> {
+    let mut combinations = vec![vec![]];
+    for position in options {
+        let mut extended = vec![];
+        for combination in &combinations {
+            for option in position {
+                let mut combination = combination.clone();
+                combination.push(option.clone());
+                extended.push(combination);
+            }
+        }
+        combinations = extended;
+    }
+    combinations
+}
+
+pub fn argument_types_valid(func: &Function, arg_types: &[RDFNodeType]) -> bool {
+    arg_types.iter().enumerate().all(|(i, t)| match func {
+        Function::Year
+        | Function::Month
+        | Function::Day
+        | Function::Hours
+        | Function::Minutes
+        | Function::Seconds => has_temporal_component(func, t),
+        Function::Timezone | Function::Tz => is_datetime_type(t),
+        Function::Abs | Function::Ceil | Function::Floor | Function::Round => is_numeric_type(t),
+        Function::Concat
+        | Function::Contains
+        | Function::StrStarts
+        | Function::StrEnds
+        | Function::StrBefore
+        | Function::StrAfter
+        | Function::StrLen
+        | Function::UCase
+        | Function::LCase
+        | Function::EncodeForUri => is_string_type(t),
+        Function::LangMatches => is_simple_string_type(t),
+        Function::Regex | Function::Replace => {
+            if i == 0 {
+                is_string_type(t)
+            } else {
+                is_simple_string_type(t)
+            }
+        }
+        Function::SubStr => {
+            if i == 0 {
+                is_string_type(t)
+            } else {
+                is_numeric_type(t)
+            }
+        }
+        Function::StrLang
+        | Function::BNode
+        | Function::Md5
+        | Function::Sha1
+        | Function::Sha256
+        | Function::Sha384
+        | Function::Sha512 => is_simple_string_type(t),
+        Function::Iri => t == &RDFNodeType::IRI || is_simple_string_type(t),
+        Function::StrDt => {
+            if i == 0 {
+                is_simple_string_type(t)
+            } else {
+                t == &RDFNodeType::IRI
+            }
+        }
+        _ => true,
+    })
+}
+
+pub fn comparable_types(op: Operator, left_type: &RDFNodeType, right_type: &RDFNodeType) -> bool {
+    if let (RDFNodeType::Literal(l1), RDFNodeType::Literal(l2)) = (left_type, right_type) {
+        (literal_is_numeric(l1.as_ref()) && literal_is_numeric(l2.as_ref()))
+            || (literal_is_boolean(l1.as_ref()) && literal_is_boolean(l2.as_ref()))
+            || (literal_is_string(l1.as_ref()) && literal_is_string(l2.as_ref()))
+            || (literal_is_datetime(l1.as_ref()) && literal_is_datetime(l2.as_ref()))
+            || (op == Operator::Eq && l1 == l2)
+    } else {
+        op == Operator::Eq && left_type == right_type
+    }
+}
+
+pub fn is_numeric_type(t: &RDFNodeType) -> bool {
+    matches!(t, RDFNodeType::Literal(l) if literal_is_numeric(l.as_ref()))
+}
+
+pub fn is_float_type(t: &RDFNodeType) -> bool {
+    matches!(t, RDFNodeType::Literal(l) if matches!(l.as_ref(), xsd::FLOAT | xsd::DOUBLE))
+}
+
+pub fn is_string_type(t: &RDFNodeType) -> bool {
+    is_simple_string_type(t) || is_lang_string_type(t)
+}
+
+pub fn is_simple_string_type(t: &RDFNodeType) -> bool {
+    matches!(t, RDFNodeType::Literal(l) if literal_is_string(l.as_ref()))
+}
+
+pub fn is_lang_string_type(t: &RDFNodeType) -> bool {
+    matches!(t, RDFNodeType::Literal(l) if l.as_ref() == rdf::LANG_STRING)
+}
+
+//Arguments to CONTAINS, STRSTARTS and STRENDS are compatible when the second argument is a
+//simple literal or has the same language tag as the first
+pub fn string_arguments_compatible(first_type: &RDFNodeType, second_type: &RDFNodeType) -> bool {
+    is_string_type(first_type)
+        && (is_simple_string_type(second_type)
+            || (is_lang_string_type(first_type) && is_lang_string_type(second_type)))
+}
+
+pub fn is_duration_type(t: &RDFNodeType) -> bool {
+    matches!(t, RDFNodeType::Literal(l) if matches!(
+        l.as_ref(),
+        xsd::DURATION | xsd::DAY_TIME_DURATION | xsd::YEAR_MONTH_DURATION
+    ))
+}
+
+//Which of the date and time types have the component that a function extracts
+fn has_temporal_component(func: &Function, t: &RDFNodeType) -> bool {
+    if let RDFNodeType::Literal(l) = t {
+        let l = l.as_ref();
+        match func {
+            Function::Year => matches!(
+                l,
+                xsd::DATE_TIME | xsd::DATE | xsd::G_YEAR | xsd::G_YEAR_MONTH
+            ),
+            Function::Month => matches!(l, xsd::DATE_TIME | xsd::DATE | xsd::G_YEAR_MONTH),
+            Function::Day => matches!(l, xsd::DATE_TIME | xsd::DATE),
+            _ => matches!(l, xsd::DATE_TIME | xsd::TIME),
+        }
+    } else {
+        false
+    }
+}
+
+pub fn is_datetime_type(t: &RDFNodeType) -> bool {
+    matches!(t, RDFNodeType::Literal(l) if literal_is_datetime(l.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxrdf::{Literal, Variable};
+
+    fn infer(
+        expression: &Expression,
+        types: Vec<(&str, RDFNodeType)>,
+    ) -> Result<InferredTypes, QueryProcessingError> {
+        let types = types.into_iter().map(|(v, t)| (v.to_string(), t)).collect();
+        infer_expression_types(
+            expression,
+            &types,
+            &CustomFunctionRegistry::default(),
+            &Context::new(),
+        )
+    }
+
+    fn var(name: &str) -> Expression {
+        Expression::Variable(Variable::new_unchecked(name))
+    }
+
+    #[test]
+    fn wrong_number_of_arguments_is_reported_statically() {
+        let string = RDFNodeType::Literal(xsd::STRING.into_owned());
+        let expression = Expression::FunctionCall(Function::StrLen, vec![var("a"), var("b")]);
+        let result = infer(&expression, vec![("a", string.clone()), ("b", string)]);
+        assert!(matches!(
+            result,
+            Err(QueryProcessingError::WrongNumberOfArguments(..))
+        ));
+    }
+
+    #[test]
+    fn multi_typed_arguments_are_inferred_per_base_type() {
+        let expression = Expression::FunctionCall(Function::StrLen, vec![var("a")]);
+        let result = infer(
+            &expression,
+            vec![("a", lang_string_or_simple_literal_type())],
+        );
+        assert_eq!(
+            result.unwrap().types.get(Context::new().as_str()),
+            Some(&RDFNodeType::Literal(xsd::INTEGER.into_owned()))
+        );
+    }
+
+    #[test]
+    fn duration_literals_are_typed_as_their_subtype() {
+        let quarter = Literal::new_typed_literal("PT15M", xsd::DURATION);
+        let expression =
+            Expression::Add(Box::new(var("t")), Box::new(Expression::Literal(quarter)));
+        let datetime = RDFNodeType::Literal(xsd::DATE_TIME.into_owned());
+        let result = infer(&expression, vec![("t", datetime.clone())]);
+        assert_eq!(
+            result.unwrap().types.get(Context::new().as_str()),
+            Some(&datetime)
+        );
+    }
+
+    #[test]
+    fn durations_mixing_months_and_days_are_unbound() {
+        let mixed = Literal::new_typed_literal("P1MT1H", xsd::DURATION);
+        let expression = Expression::Add(Box::new(var("t")), Box::new(Expression::Literal(mixed)));
+        let datetime = RDFNodeType::Literal(xsd::DATE_TIME.into_owned());
+        let result = infer(&expression, vec![("t", datetime)]).unwrap();
+        assert_eq!(
+            result.types.get(Context::new().as_str()),
+            Some(&RDFNodeType::None)
+        );
+        assert!(result.type_errors.is_empty());
+    }
+
+    #[test]
+    fn mixed_temporal_results_give_a_multi_type() {
+        let hour = Literal::new_typed_literal("PT1H", xsd::DAY_TIME_DURATION);
+        let expression = Expression::Add(Box::new(var("m")), Box::new(Expression::Literal(hour)));
+        let datetime = BaseRDFNodeType::Literal(xsd::DATE_TIME.into_owned());
+        let duration = BaseRDFNodeType::Literal(xsd::DAY_TIME_DURATION.into_owned());
+        let mut expected = vec![datetime.clone(), duration.clone()];
+        expected.sort();
+        let result = infer(
+            &expression,
+            vec![("m", RDFNodeType::MultiType(vec![datetime, duration]))],
+        );
+        assert_eq!(
+            result.unwrap().types.get(Context::new().as_str()),
+            Some(&RDFNodeType::MultiType(expected))
+        );
+    }
+
+    #[test]
+    fn strdt_with_a_variable_datatype_is_unbound() {
+        let expression = Expression::FunctionCall(
+            Function::StrDt,
+            vec![
+                Expression::Literal(Literal::new_simple_literal("1")),
+                var("dt"),
+            ],
+        );
+        let result = infer(&expression, vec![("dt", RDFNodeType::IRI)]).unwrap();
+        assert_eq!(
+            result.types.get(Context::new().as_str()),
+            Some(&RDFNodeType::None)
+        );
+        assert!(result.type_errors.is_empty());
+    }
+
+    #[test]
+    fn type_errors_are_collected_and_unbound() {
+        let expression = Expression::Add(
+            Box::new(Expression::Literal(Literal::new_simple_literal("abc"))),
+            Box::new(Expression::Literal(Literal::from(1))),
+        );
+        let outer = Expression::FunctionCall(Function::StrLen, vec![expression.clone()]);
+        let inferred = infer(&outer, vec![]).unwrap();
+        let inner_context = Context::new().extension_with(PathEntry::FunctionCall(0));
+        assert_eq!(
+            inferred.types.get(inner_context.as_str()),
+            Some(&RDFNodeType::None)
+        );
+        //The unbound argument of STRLEN is not reported again
+        assert_eq!(inferred.type_errors.len(), 1);
+        assert!(matches!(
+            &inferred.type_errors[0],
+            QueryProcessingError::IncompatibleTypes(c, e, _)
+                if c == inner_context.as_str() && e == &expression.to_string()
+        ));
+    }
+}
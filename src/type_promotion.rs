@@ -0,0 +1,106 @@
+use oxrdf::vocab::xsd;
+use oxrdf::{NamedNode, NamedNodeRef};
+use polars::datatypes::DataType;
+use polars::prelude::Operator;
+
+pub fn numeric_rank(nn: NamedNodeRef) -> Option<u8> {
+    match nn {
+        xsd::INTEGER
+        | xsd::NON_POSITIVE_INTEGER
+        | xsd::NEGATIVE_INTEGER
+        | xsd::LONG
+        | xsd::INT
+        | xsd::SHORT
+        | xsd::BYTE
+        | xsd::NON_NEGATIVE_INTEGER
+        | xsd::UNSIGNED_LONG
+        | xsd::UNSIGNED_INT
+        | xsd::UNSIGNED_SHORT
+        | xsd::UNSIGNED_BYTE
+        | xsd::POSITIVE_INTEGER => Some(0),
+        xsd::DECIMAL => Some(1),
+        xsd::FLOAT => Some(2),
+        xsd::DOUBLE => Some(3),
+        _ => None,
+    }
+}
+
+pub fn is_integer_type(nn: NamedNodeRef) -> bool {
+    numeric_rank(nn) == Some(0)
+}
+
+// Follows the XPath numeric type promotion: integer subtypes -> decimal -> float -> double.
+// Integer subtypes are promoted to xsd:integer, and division of integers gives xsd:decimal.
+pub fn promote_numeric_types(
+    left: NamedNodeRef,
+    right: NamedNodeRef,
+    division: bool,
+) -> Option<NamedNode> {
+    let rank = numeric_rank(left)?.max(numeric_rank(right)?);
+    let promoted = match rank {
+        0 if division => xsd::DECIMAL,
+        0 => xsd::INTEGER,
+        1 => xsd::DECIMAL,
+        2 => xsd::FLOAT,
+        _ => xsd::DOUBLE,
+    };
+    Some(promoted.into_owned())
+}
+
+//The common type of the numeric types among several, for instance the results of arithmetic on
+//the base types of multi typed values
+pub fn promote_all_numeric_types<'a>(
+    types: impl IntoIterator<Item = NamedNodeRef<'a>>,
+) -> Option<NamedNode> {
+    let mut promoted: Option<NamedNode> = None;
+    for t in types {
+        if numeric_rank(t).is_some() {
+            promoted = Some(match promoted {
+                Some(p) => promote_numeric_types(p.as_ref(), t, false).unwrap_or(p),
+                None => t.into_owned(),
+            });
+        }
+    }
+    promoted
+}
+
+pub fn numeric_polars_dtype(nn: NamedNodeRef) -> DataType {
+    match nn {
+        xsd::LONG => DataType::Int64,
+        xsd::INT => DataType::Int32,
+        xsd::SHORT => DataType::Int16,
+        xsd::BYTE => DataType::Int8,
+        xsd::UNSIGNED_LONG => DataType::UInt64,
+        xsd::UNSIGNED_INT => DataType::UInt32,
+        xsd::UNSIGNED_SHORT => DataType::UInt16,
+        xsd::UNSIGNED_BYTE => DataType::UInt8,
+        xsd::FLOAT => DataType::Float32,
+        xsd::DOUBLE | xsd::DECIMAL => DataType::Float64,
+        _ => DataType::Int64,
+    }
+}
+
+//Result types of arithmetic on xsd:dateTime and the totally ordered duration subtypes
+pub fn temporal_operator_type(
+    op: Operator,
+    left: NamedNodeRef,
+    right: NamedNodeRef,
+) -> Option<NamedNode> {
+    let is_duration =
+        |nn: NamedNodeRef| matches!(nn, xsd::DAY_TIME_DURATION | xsd::YEAR_MONTH_DURATION);
+    let result = match op {
+        Operator::Plus if left == xsd::DATE_TIME && is_duration(right) => xsd::DATE_TIME,
+        Operator::Plus if is_duration(left) && right == xsd::DATE_TIME => xsd::DATE_TIME,
+        Operator::Minus if left == xsd::DATE_TIME && is_duration(right) => xsd::DATE_TIME,
+        Operator::Minus if left == xsd::DATE_TIME && right == xsd::DATE_TIME => {
+            xsd::DAY_TIME_DURATION
+        }
+        Operator::Plus | Operator::Minus if is_duration(left) && left == right => left,
+        Operator::Multiply if is_duration(left) && numeric_rank(right).is_some() => left,
+        Operator::Multiply if numeric_rank(left).is_some() && is_duration(right) => right,
+        Operator::Divide if is_duration(left) && numeric_rank(right).is_some() => left,
+        Operator::Divide if is_duration(left) && left == right => xsd::DECIMAL,
+        _ => return None,
+    };
+    Some(result.into_owned())
+}